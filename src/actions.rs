@@ -1,76 +1,342 @@
+use std::hash::{Hash, Hasher};
+
 use bevy::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::{ActionComponent, Movable, MovementData};
+use crate::{spawn_projectile, ProjectileIds, ActiveGrab, GrabEffect, Throw, Contact, Move, MoveCategory, Fixed, FixedVec2, Inputs, AnimationData, AnimationMode, Player, SpriteAtlas, SpriteRes, ActionComponent, HitboxEffect, Hitboxes, Movable, MovementData, ActiveHitbox, HurtboxModifier, Hurtboxes, ActiveHurtboxModifier, FighterState};
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
 pub struct Action {
     pub animation_speed: f32,
+    #[serde(default)]
+    pub animation_mode: AnimationMode,
     pub sprite: String,
+    //starts the sprite over even if it's already playing, otherwise it carries on (walking starts a new action every frame)
+    #[serde(default)]
+    pub restart_animation: bool,
     pub duration: usize,
     pub start_effects: Vec<Effect>,
     pub effects: Vec<Effect>,
     pub end_effects: Vec<Effect>,
+    #[serde(default)]
+    pub cancels: Vec<CancelWindow>,
+    #[serde(default)]
+    pub invulnerability: Vec<Invulnerability>,
+    #[serde(default)]
+    pub armor: Option<Armor>,
+    //getting hit during these frames is a counter hit, meant for startup
+    #[serde(default)]
+    pub counter_hit: Option<CounterHit>,
+    #[serde(skip)]
+    pub started: bool,
+    //how many frames this action has run for
+    #[serde(skip)]
+    pub frame: i32,
+}
+
+//only hashes what changes while the action runs, the rest comes straight from the fighter data
+impl Hash for Action {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sprite.hash(state);
+        self.started.hash(state);
+        self.frame.hash(state);
+        self.start_effects.len().hash(state);
+        for effect in &self.effects {
+            std::mem::discriminant(effect).hash(state);
+            if let Effect::Wait(counter) = effect {
+                counter.hash(state);
+            }
+        }
+        self.armor.as_ref().map(|armor| armor.hits).hash(state);
+    }
+}
+
+//lets another move interrupt this action between start and end (inclusive, counted from the action's first frame as 1)
+#[derive(Debug, Clone, Deserialize, Serialize, Reflect)]
+pub struct CancelWindow {
+    pub start: i32,
+    pub end: i32,
+    //a category also allows everything above it, so normals can cancel into specials and supers
+    #[serde(default)]
+    pub categories: Vec<MoveCategory>,
+    #[serde(default)]
+    pub moves: Vec<String>,
+    #[serde(default = "default_true")]
+    pub on_hit: bool,
+    #[serde(default = "default_true")]
+    pub on_block: bool,
+    #[serde(default)]
+    pub on_whiff: bool
+}
+
+pub fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttackKind {
+    Strike,
+    Throw,
+    Projectile
+}
+
+//frames are counted the same way as cancel windows
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct Invulnerability {
+    pub start: i32,
+    pub end: i32,
+    #[serde(default)]
+    pub strike: bool,
+    #[serde(default)]
+    pub throw: bool,
+    #[serde(default)]
+    pub projectile: bool
+}
+
+impl Invulnerability {
+    pub fn covers(&self, frame: i32, kind: AttackKind) -> bool {
+        if frame < self.start || frame > self.end {
+            return false;
+        }
+        return match kind {
+            AttackKind::Strike => self.strike,
+            AttackKind::Throw => self.throw,
+            AttackKind::Projectile => self.projectile
+        };
+    }
+}
+
+//takes the damage from up to hits hits without being interrupted, hits counts down as they're absorbed
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct Armor {
+    pub start: i32,
+    pub end: i32,
+    pub hits: i32
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct CounterHit {
+    pub start: i32,
+    pub end: i32
+}
+
+impl Action {
+    pub fn counter_hittable(&self) -> bool {
+        return self.counter_hit.as_ref().is_some_and(|window| self.frame >= window.start && self.frame <= window.end);
+    }
+    pub fn invulnerable_to(&self, kind: AttackKind) -> bool {
+        return self.invulnerability.iter().any(|window| window.covers(self.frame, kind));
+    }
+    pub fn absorb_hit(&mut self) -> bool {
+        let frame = self.frame;
+        if let Some(armor) = &mut self.armor {
+            if frame >= armor.start && frame <= armor.end && armor.hits > 0 {
+                armor.hits -= 1;
+                return true;
+            }
+        }
+        return false;
+    }
+}
+
+impl Default for CancelWindow {
+    fn default() -> Self {
+        CancelWindow { start: 1, end: 1, categories: vec![], moves: vec![], on_hit: true, on_block: true, on_whiff: false }
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+impl CancelWindow {
+    pub fn allows(&self, frame: i32, contact: Contact, fighter_move: &Move) -> bool {
+        if frame < self.start || frame > self.end {
+            return false;
+        }
+        let contact_allowed = match contact {
+            Contact::Hit => self.on_hit,
+            Contact::Block => self.on_block,
+            Contact::Whiff => self.on_whiff
+        };
+        if !contact_allowed {
+            return false;
+        }
+        return self.moves.contains(&fighter_move.name) || 
+            self.categories.iter().any(|category| category.cancels_into(&fighter_move.category));
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Reflect)]
 pub enum Effect {
     Move(MovementEffect),
     Wait(i32),
     WaitForGround,
-    SetYSpeed(f32),
-    AddYSpeed(f32),
+    SetYSpeed(Fixed),
+    AddYSpeed(Fixed),
+    Hitbox(HitboxEffect),
+    ModifyHurtbox(HurtboxModifier),
+    Knockdown(i32),
+    //ends the stun of whoever the move last hit, or whoever is being thrown
+    ResetHitstun,
+    SetSprite(SpriteEffect),
+    CallMoveIfDirectionHeld(DirectionCondition),
+    CallMoveIfDirectionPressed(DirectionCondition),
+    CallMoveIfButtonHeld(ButtonCondition),
+    CallMoveIfButtonPressed(ButtonCondition),
+    CallMoveIfButtonReleased(ButtonCondition),
+    SpawnProjectile(String),
+    Grab(GrabEffect),
+    SetVictimOffset(FixedVec2),
+    SetVictimSprite(SpriteEffect),
     //SetGravity,
-    /*Hitbox,
-    ModifyHurtbox,
-    SetSprite,
-    CallMoveIfDirectionHeld,
-    CallMoveIfDirectionPressed,
-    CallMoveIfButtonHeld,
-    CallMoveIfButtonPressed,
-    CallMoveIfButtonReleased,
-    Knockdown,
-    ResetHitstun*/
-}
-
-#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize)]
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize, Serialize, Reflect)]
 pub struct MovementEffect {
-    pub distance: f32,
+    pub distance: Fixed,
     pub duration: i32,
-    pub ease: f32,
-    pub direction: Vec2
+    pub ease: Fixed,
+    pub direction: FixedVec2
 }
 
-pub fn parse_actions(mut entities: Query<(&mut ActionComponent, Option<&mut Movable>)>) {
-    for (mut actions, mut moveable_opt) in &mut entities {
+//switches animation partway through an action, frame picks where the new animation starts
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct SpriteEffect {
+    pub sprite: String,
+    #[serde(default)]
+    pub frame: Option<usize>,
+    #[serde(default)]
+    pub speed: f32,
+    #[serde(default)]
+    pub mode: AnimationMode
+}
+
+impl Hash for SpriteEffect {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.sprite.hash(state);
+        self.frame.hash(state);
+        self.speed.to_bits().hash(state);
+        self.mode.hash(state);
+    }
+}
+
+//direction is in numpad notation, relative to where the fighter is facing
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct DirectionCondition {
+    pub direction: u8,
+    pub move_name: String
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Reflect)]
+pub struct ButtonCondition {
+    pub button: Inputs,
+    pub move_name: String
+}
+
+pub struct EffectTarget<'a> {
+    pub movable: Option<Mut<'a, Movable>>,
+    pub hitboxes: Option<Mut<'a, Hitboxes>>,
+    pub hurtboxes: Option<Mut<'a, Hurtboxes>>,
+    pub state: Option<Mut<'a, FighterState>>,
+    pub animation: Option<Mut<'a, AnimationData>>,
+    pub atlas: Option<&'a SpriteAtlas>,
+    pub throw: Option<Mut<'a, Throw>>,
+    //set by the CallMoveIf effects, the move replaces the whole action stack once the effects are done
+    pub called_move: Option<String>,
+    //projectiles need Commands to spawn, so they're spawned once the effects are done
+    pub projectiles: Vec<String>,
+    //the victim is another entity, so their stun is reset once every action has run
+    pub reset_victim_hitstun: bool,
+    //stack depth and frame of the action being run, grabs keep them to tell if their action is still going
+    pub action_depth: usize,
+    pub action_frame: i32
+}
+
+pub fn parse_actions(
+    mut commands: Commands,
+    mut entities: Query<(&mut ActionComponent, Option<&mut Movable>, Option<&mut Hitboxes>, Option<&mut Hurtboxes>, Option<&mut FighterState>, Option<&mut AnimationData>, Option<&mut Throw>, Option<&Player>)>,
+    sprites: Res<SpriteRes>,
+    mut projectile_ids: ResMut<ProjectileIds>
+) {
+    let mut stun_resets: Vec<usize> = vec![];
+    for (mut actions, movable, hitboxes, hurtboxes, state, animation, throw, player) in &mut entities {
+        let atlas = player.and_then(|player| sprites.atlases.get(&player.fighter.name.to_lowercase()));
+        let mut target = EffectTarget { movable, hitboxes, hurtboxes, state, animation, atlas, throw, called_move: None, projectiles: vec![], reset_victim_hitstun: false, action_depth: 0, action_frame: 0 };
         let mut action_over = true;
+        let action_depth = actions.actions.len();
         if let Some(action) = actions.actions.last_mut() {
+            action.frame += 1;
+            target.action_depth = action_depth;
+            target.action_frame = action.frame;
+            if !action.started {
+                action.started = true;
+                if let (Some(animation), Some(atlas)) = (&mut target.animation, target.atlas) {
+                    if action.restart_animation {
+                        animation.play(&action.sprite, action.animation_speed, action.animation_mode, atlas);
+                    } else {
+                        animation.set_animation(&action.sprite, action.animation_speed, action.animation_mode, atlas);
+                    }
+                }
+            }
             if action.start_effects.len() > 0 {
                 for effect in &mut action.start_effects {
-                    parse_effect(effect, &mut moveable_opt);
+                    parse_effect(effect, &mut target);
                 }
                 action_over = false;
                 action.start_effects.clear();
             } else {
                 for effect in &mut action.effects {
-                    if parse_effect(effect, &mut moveable_opt) {
+                    if parse_effect(effect, &mut target) {
                         action_over = false;
                     }
                 }
             }
         }
+        if target.reset_victim_hitstun {
+            //whoever is being thrown, otherwise whoever the move last hit
+            let victim = target.throw.as_ref().and_then(|throw| throw.victim).or(actions.victim);
+            if let Some(victim) = victim {
+                stun_resets.push(victim);
+            }
+        }
+        if let (Some(player), Some(movable), Some(atlas)) = (player, &target.movable, target.atlas) {
+            for projectile_name in &target.projectiles {
+                if let Some(info) = player.fighter.projectiles.iter().find(|info| info.name == *projectile_name) {
+                    spawn_projectile(&mut commands, &mut projectile_ids, info, player, movable, atlas);
+                } else {
+                    warn!("Tried to spawn projectile {}, but {} doesn't have it", projectile_name, player.fighter.name);
+                }
+            }
+        }
+        if let (Some(move_name), Some(player)) = (target.called_move.take(), player) {
+            if let Some(called_move) = player.fighter.moves.iter().find(|fighter_move| fighter_move.name == move_name) {
+                debug!("Move {} started!", called_move.name);
+                actions.start_move(called_move);
+                if let Some(movable) = &mut target.movable {
+                    movable.input.clear_buffer();
+                }
+                continue;
+            } else {
+                warn!("Tried to call move {}, but {} doesn't have it", move_name, player.fighter.name);
+            }
+        }
         if action_over {
             if let Some(action) = actions.actions.last_mut() {
                 for effect in &mut action.end_effects {
-                    parse_effect(effect, &mut moveable_opt);
+                    parse_effect(effect, &mut target);
                 }
             }
             actions.actions.pop();
         }
     }
+
+    for (_, movable, _, _, state, _, _, player) in &mut entities {
+        if let (Some(mut state), Some(player)) = (state, player) {
+            if stun_resets.contains(&player.handle) && state.in_stun() {
+                *state = FighterState::neutral(movable.map_or(true, |movable| movable.grounded));
+            }
+        }
+    }
 }
 
-fn parse_effect(effect: &mut Effect, mut moveable_opt: &mut Option<Mut<Movable>>) -> bool {
+fn parse_effect(effect: &mut Effect, target: &mut EffectTarget) -> bool {
     match effect {
         Effect::Wait(counter) => {
             *counter = *counter - 1;
@@ -79,28 +345,168 @@ fn parse_effect(effect: &mut Effect, mut moveable_opt: &mut Option<Mut<Movable>>
             }
         }
         Effect::WaitForGround => {
-            if let Some(moveable) = &mut moveable_opt {
+            if let Some(moveable) = &mut target.movable {
                 if !moveable.grounded {
                     return true;
                 }
             }
         }
         Effect::Move(movement_effect) => {
-            if let Some(moveable) = &mut moveable_opt {
-                moveable.movements.push(MovementData::from_movement_effect(movement_effect.to_owned()));
+            if let Some(moveable) = &mut target.movable {
+                let facing = moveable.facing;
+                moveable.movements.push(MovementData::from_movement_effect(movement_effect.to_owned(), &facing));
             }
         }
         Effect::SetYSpeed(yspeed) => {
-            if let Some(moveable) = &mut moveable_opt {
+            if let Some(moveable) = &mut target.movable {
                 moveable.yspeed = *yspeed;
             }
         }
         Effect::AddYSpeed(yspeed) => {
-            if let Some(moveable) = &mut moveable_opt {
+            if let Some(moveable) = &mut target.movable {
                 moveable.yspeed += *yspeed;
             }
         }
-        _ => {}
+        Effect::Hitbox(hitbox) => {
+            if let Some(hitboxes) = &mut target.hitboxes {
+                let mut hitbox = hitbox.to_owned();
+                if let Some(moveable) = &target.movable {
+                    hitbox.offset.x *= moveable.facing.sign();
+                }
+                hitboxes.boxes.push(ActiveHitbox { hitbox, frame: 0, has_hit: false });
+            }
+        }
+        Effect::ModifyHurtbox(modifier) => {
+            if let Some(hurtboxes) = &mut target.hurtboxes {
+                hurtboxes.modifiers.push(ActiveHurtboxModifier { modifier: modifier.to_owned(), frame: 0 });
+            }
+        }
+        Effect::Knockdown(frames) => {
+            if let Some(state) = &mut target.state {
+                **state = FighterState::Knockdown(*frames);
+            }
+        }
+        Effect::SetSprite(sprite_effect) => {
+            if let (Some(animation), Some(atlas)) = (&mut target.animation, target.atlas) {
+                animation.play(&sprite_effect.sprite, sprite_effect.speed, sprite_effect.mode, atlas);
+                if let Some(frame) = sprite_effect.frame {
+                    animation.set_frame(frame);
+                }
+            }
+        }
+        Effect::CallMoveIfDirectionHeld(condition) => {
+            if let Some(moveable) = &target.movable {
+                if moveable.input.current().relative_to(&moveable.facing).has_dir(&condition.direction) {
+                    call_move(target, &condition.move_name);
+                }
+            }
+        }
+        Effect::CallMoveIfDirectionPressed(condition) => {
+            if let Some(moveable) = &target.movable {
+                let facing = moveable.facing;
+                if moveable.input.current().relative_to(&facing).has_dir(&condition.direction) && 
+                    !moveable.input.previous().relative_to(&facing).has_dir(&condition.direction) {
+                    call_move(target, &condition.move_name);
+                }
+            }
+        }
+        Effect::CallMoveIfButtonHeld(condition) => {
+            if let Some(moveable) = &target.movable {
+                if moveable.input.current().has(&condition.button) {
+                    call_move(target, &condition.move_name);
+                }
+            }
+        }
+        Effect::CallMoveIfButtonPressed(condition) => {
+            if let Some(moveable) = &target.movable {
+                if moveable.input.pressed().has(&condition.button) {
+                    call_move(target, &condition.move_name);
+                }
+            }
+        }
+        Effect::CallMoveIfButtonReleased(condition) => {
+            if let Some(moveable) = &target.movable {
+                if moveable.input.released().has(&condition.button) {
+                    call_move(target, &condition.move_name);
+                }
+            }
+        }
+        Effect::SpawnProjectile(projectile_name) => {
+            target.projectiles.push(projectile_name.to_owned());
+        }
+        Effect::Grab(grab) => {
+            if let Some(throw) = &mut target.throw {
+                let mut grab = grab.to_owned();
+                if let Some(moveable) = &target.movable {
+                    grab.offset.x *= moveable.facing.sign();
+                }
+                throw.grabs.push(ActiveGrab { grab, frame: 0, action_depth: target.action_depth, action_frame: target.action_frame });
+            }
+        }
+        Effect::SetVictimOffset(offset) => {
+            if let Some(throw) = &mut target.throw {
+                throw.victim_offset = *offset;
+            }
+        }
+        Effect::SetVictimSprite(sprite_effect) => {
+            if let Some(throw) = &mut target.throw {
+                throw.victim_sprite = Some(sprite_effect.to_owned());
+            }
+        }
+        Effect::ResetHitstun => {
+            target.reset_victim_hitstun = true;
+        }
     }
     return false;
-}
\ No newline at end of file
+}
+
+//the first move called in a frame wins
+fn call_move(target: &mut EffectTarget, move_name: &str) {
+    if target.called_move.is_none() {
+        target.called_move = Some(move_name.to_owned());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::utils::HashMap;
+    use crate::{spawn_test_fighter, Fighter};
+
+    fn test_app() -> App {
+        let mut app = App::new();
+        app.insert_resource(SpriteRes { atlases: HashMap::new() });
+        app.init_resource::<ProjectileIds>();
+        app.add_systems(Update, parse_actions);
+        return app;
+    }
+
+    fn reset_action() -> Vec<Action> {
+        return vec![Action { effects: vec![Effect::ResetHitstun, Effect::Wait(10)], ..default() }];
+    }
+
+    #[test]
+    fn reset_hitstun_targets_the_hit_victim() {
+        let mut app = test_app();
+        let attacker = spawn_test_fighter(&mut app.world, 0, Fighter::default());
+        app.world.entity_mut(attacker).insert(ActionComponent { actions: reset_action(), contact: Contact::Hit, victim: Some(1) });
+        let victim = spawn_test_fighter(&mut app.world, 1, Fighter::default());
+        app.world.entity_mut(victim).insert(FighterState::Hitstun(20));
+        app.update();
+        assert_eq!(*app.world.get::<FighterState>(victim).unwrap(), FighterState::Standing);
+    }
+
+    #[test]
+    fn reset_hitstun_targets_the_throw_victim() {
+        let mut app = test_app();
+        let attacker = spawn_test_fighter(&mut app.world, 0, Fighter::default());
+        app.world.entity_mut(attacker).insert((ActionComponent { actions: reset_action(), ..default() }, Throw { victim: Some(1), ..default() }));
+        let bystander = spawn_test_fighter(&mut app.world, 2, Fighter::default());
+        app.world.entity_mut(bystander).insert(FighterState::Hitstun(20));
+        let victim = spawn_test_fighter(&mut app.world, 1, Fighter::default());
+        app.world.entity_mut(victim).insert(FighterState::Hitstun(20));
+        app.update();
+        assert_eq!(*app.world.get::<FighterState>(victim).unwrap(), FighterState::Standing);
+        assert_eq!(*app.world.get::<FighterState>(bystander).unwrap(), FighterState::Hitstun(20));
+    }
+}
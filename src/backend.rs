@@ -1,4 +1,4 @@
-use bevy::{prelude::*, utils::{HashMap, BoxedFuture}, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}};
+use bevy::{prelude::*, utils::{HashMap, BoxedFuture}, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}, sprite::Anchor};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, TypeUuid, TypePath)]
@@ -21,6 +21,9 @@ impl AssetLoader for AnimationInfoLoader {
 
             let mut revised_hashmap = HashMap::new();
             for (animation_name, animation_info) in custom_asset.animations {
+                if let Err(error) = animation_info.validate() {
+                    return Err(bevy::asset::Error::msg(format!("{}: animation \"{}\" {}", load_context.path().display(), animation_name, error)));
+                }
                 revised_hashmap.insert(animation_name.to_lowercase(), animation_info);
             }
 
@@ -41,35 +44,126 @@ struct SpriteInfo {
     x_offset: u32,
     y_offset: u32,
     x_frames: u32,
-    y_frames: u32
+    y_frames: u32,
+    //for sheets where the last row isn't full, defaults to x_frames * y_frames
+    #[serde(default)]
+    frame_count: Option<u32>,
+    #[serde(default)]
+    frames: Vec<FrameInfo>
+}
+
+//frames without an entry here play for the default duration and are anchored at their center
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy)]
+struct FrameInfo {
+    //in simulation frames, 0 uses the default
+    #[serde(default)]
+    duration: u32,
+    //in pixels from the top left of the frame, usually the point between the feet
+    #[serde(default)]
+    anchor: Option<Vec2>
+}
+
+impl SpriteInfo {
+    fn frame_count(&self) -> u32 {
+        self.frame_count.unwrap_or(self.x_frames * self.y_frames)
+    }
+    fn validate(&self) -> Result<(), String> {
+        if self.x_frames == 0 || self.y_frames == 0 {
+            return Err(format!("needs at least 1 x_frame and y_frame, got {}x{}", self.x_frames, self.y_frames));
+        }
+        if self.frame_count() == 0 || self.frame_count() > self.x_frames * self.y_frames {
+            return Err(format!("has a frame_count of {} but the sheet is {}x{}", self.frame_count(), self.x_frames, self.y_frames));
+        }
+        if self.frames.len() > self.frame_count() as usize {
+            return Err(format!("lists {} frames but only has {}", self.frames.len(), self.frame_count()));
+        }
+        return Ok(());
+    }
+    //the frame size comes from the sheet, so this can only run once the image is loaded
+    fn validate_anchors(&self, width: u32, height: u32) -> Result<(), String> {
+        for (i, frame) in self.frames.iter().enumerate() {
+            if let Some(anchor) = frame.anchor {
+                if anchor.x < 0.0 || anchor.y < 0.0 || anchor.x > width as f32 || anchor.y > height as f32 {
+                    return Err(format!("frame {}: anchor {:?} is outside of its {}x{} frame", i, anchor, width, height));
+                }
+            }
+        }
+        return Ok(());
+    }
+}
+
+//how many simulation frames each sprite frame is shown for at animation_speed 1
+const DEFAULT_FRAMES_PER_SPRITE: u32 = 4;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Hash, Deserialize, Serialize, Reflect)]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    Hold
 }
 
-#[derive(Component)]
+//animations are counted in simulation frames so they roll back along with everything else
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct AnimationData {
-    timer: Timer,
+    pub frame: u32,
+    pub durations: Vec<u32>,
+    pub mode: AnimationMode,
     pub index: usize,
     pub just_finished: bool,
     pub animation_name: String,
     anim_handles: Vec<Handle<Image>>,
+    anchors: Vec<Vec2>,
 }
 
 impl AnimationData {
     pub fn new(animation_name: String, atlas: &SpriteAtlas) -> AnimationData {
-        if let Some(animation_data) = atlas.animation_data.get(&animation_name.to_lowercase()) {
-            return AnimationData { 
-                timer: Timer::from_seconds(2./30., TimerMode::Repeating), 
-                index: 0,
-                just_finished: false, 
-                anim_handles: animation_data.anim_handles.to_owned(), 
-                animation_name };
+        let (anim_handles, durations, anchors) = match atlas.animation_data.get(&animation_name.to_lowercase()) {
+            Some(animation_data) => (
+                animation_data.anim_handles.to_owned(),
+                animation_data.durations.iter().map(|duration| scaled_duration(*duration, 1.0)).collect(),
+                animation_data.anchors.to_owned()
+            ),
+            None => (vec![], vec![], vec![])
+        };
+        return AnimationData { 
+            frame: 0,
+            durations,
+            mode: AnimationMode::Loop,
+            index: 0,
+            just_finished: false, 
+            anim_handles, 
+            anchors,
+            animation_name };
+    }
+    //only switches when the animation changes, so states can call this every frame
+    pub fn set_animation(&mut self, animation_name: &str, speed: f32, mode: AnimationMode, atlas: &SpriteAtlas) {
+        //fall back to the idle animation for fighters that don't have a sprite for this yet
+        let animation_name = if atlas.animation_data.contains_key(&animation_name.to_lowercase()) {
+            animation_name
+        } else {
+            "idle"
+        };
+        if self.animation_name.to_lowercase() != animation_name.to_lowercase() {
+            self.play(animation_name, speed, mode, atlas);
+        }
+    }
+    //always starts the animation over from its first frame
+    pub fn play(&mut self, animation_name: &str, speed: f32, mode: AnimationMode, atlas: &SpriteAtlas) {
+        let animation_name = if atlas.animation_data.contains_key(&animation_name.to_lowercase()) {
+            animation_name
         } else {
-            return AnimationData { 
-                timer: Timer::from_seconds(0.2, TimerMode::Repeating), 
-                index: 0,
-                just_finished: false, 
-                anim_handles: vec![], 
-                animation_name };
+            "idle"
+        };
+        *self = AnimationData::new(animation_name.to_owned(), atlas);
+        if let Some(animation_data) = atlas.animation_data.get(&animation_name.to_lowercase()) {
+            self.durations = animation_data.durations.iter().map(|duration| scaled_duration(*duration, speed)).collect();
         }
+        self.mode = mode;
+    }
+    pub fn set_frame(&mut self, index: usize) {
+        self.index = index.min(self.anim_handles.len().max(1) - 1);
+        self.frame = 0;
     }
     pub fn get_atlas_index(&self, atlas: &SpriteAtlas,
         texture_atlases: &Res<Assets<TextureAtlas>>,) -> Option<usize> {
@@ -91,9 +185,21 @@ pub struct SpriteAtlas {
     pub animation_data: HashMap<String, SpriteData>
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SpriteData {
     anim_handles: Vec<Handle<Image>>,
+    //0 uses the default duration
+    durations: Vec<u32>,
+    //relative to the center of the frame, the same way bevy's Anchor::Custom works
+    anchors: Vec<Vec2>,
+}
+
+impl SpriteData {
+    fn push_frame(&mut self, handle: Handle<Image>, duration: u32, anchor: Vec2) {
+        self.anim_handles.push(handle);
+        self.durations.push(duration);
+        self.anchors.push(anchor);
+    }
 }
 
 pub fn spriteset_setup(
@@ -124,45 +230,43 @@ pub fn spriteset_setup(
                                             if anim_info.animations.contains_key(&file_name) {
                                                 if let Ok(sprite) = texture.to_owned().try_into_dynamic(){
                                                     let mut i = 0;
-                                                    let SpriteInfo{x_frames, y_frames, x_offset, y_offset} = anim_info.animations[&file_name];
+                                                    let sprite_info = &anim_info.animations[&file_name];
+                                                    let &SpriteInfo{x_frames, y_frames, x_offset, y_offset, ..} = sprite_info;
                                                     let (width, height) = (sprite.width() / x_frames.max(1), sprite.height() / y_frames.max(1));
-                                                    while i < anim_info.animations[&file_name].x_frames {
+                                                    if let Err(error) = sprite_info.validate_anchors(width, height) {
+                                                        error!("{}: animation \"{}\" {}", assetpath.path().display(), file_name, error);
+                                                        continue;
+                                                    }
+                                                    while i < sprite_info.frame_count() {
                                                         let texture_handle = textures.add(
                                                             Image::from_dynamic(sprite.crop_imm(
                                                                 x_offset + width * (i % x_frames), 
-                                                                y_offset + height * (((i / x_frames) as f32).floor() as u32), 
+                                                                y_offset + height * (i / x_frames), 
                                                                 width, 
                                                                 height), 
                                                                 true)
                                                         );
+                                                        let frame_info = sprite_info.frames.get(i as usize).copied().unwrap_or_default();
+                                                        let anchor = match frame_info.anchor {
+                                                            Some(anchor) => Vec2::new(anchor.x / width as f32 - 0.5, 0.5 - anchor.y / height as f32),
+                                                            None => Vec2::ZERO
+                                                        };
                                                         let texture = textures.get(&texture_handle).unwrap();
                                                         atlas.0.add_texture(texture_handle.to_owned(), texture);
-                                                        if let Some(anim_info) = atlas.1.get_mut(&file_name) {
-                                                            anim_info.anim_handles.push(texture_handle);
-                                                        }else {
-                                                            atlas.1.insert(file_name.to_owned(), SpriteData { anim_handles: vec![texture_handle] });
-                                                        }
+                                                        atlas.1.entry(file_name.to_owned()).or_default().push_frame(texture_handle, frame_info.duration, anchor);
                                                         atlas.2 += 1;
                                                         i += 1;
                                                     }
                                                 }
                                             } else {
                                                 atlas.0.add_texture(handle.to_owned(), texture);
-                                                if let Some(anim_info) = atlas.1.get_mut(&file_name) {
-                                                    anim_info.anim_handles.push(handle);
-                                                }else {
-                                                    atlas.1.insert(file_name.to_owned(), SpriteData { anim_handles: vec![handle] });
-                                                }
+                                                atlas.1.entry(file_name.to_owned()).or_default().push_frame(handle, 0, Vec2::ZERO);
                                                 atlas.2 += 1;
                                             }
                                         }
                                         _ => {
                                             atlas.0.add_texture(handle.to_owned(), texture);
-                                            if let Some(anim_info) = atlas.1.get_mut(&file_name) {
-                                                anim_info.anim_handles.push(handle);
-                                            }else {
-                                                atlas.1.insert(file_name.to_owned(), SpriteData { anim_handles: vec![handle] });
-                                            }
+                                            atlas.1.entry(file_name.to_owned()).or_default().push_frame(handle, 0, Vec2::ZERO);
                                             atlas.2 += 1;
                                         }
                                     }
@@ -189,27 +293,63 @@ pub fn spriteset_setup(
     }
 }
 
-pub fn animation_system(
+//0 means the frame or action didn't set one, so the default is used
+fn scaled_duration(duration: u32, speed: f32) -> u32 {
+    let duration = if duration == 0 { DEFAULT_FRAMES_PER_SPRITE } else { duration };
+    if speed <= 0.0 {
+        return duration;
+    }
+    return ((duration as f32 / speed).round() as u32).max(1);
+}
+
+//runs in the simulation schedule
+pub fn animation_system(mut q_entities: Query<&mut AnimationData>){
+    for mut animdata in &mut q_entities {
+        if animdata.anim_handles.len() == 0 {
+            continue;
+        }
+        animdata.frame += 1;
+        let duration = animdata.durations.get(animdata.index).copied().unwrap_or(DEFAULT_FRAMES_PER_SPRITE);
+        if animdata.frame < duration {
+            continue;
+        }
+        animdata.frame = 0;
+        if animdata.index >= animdata.anim_handles.len() - 1 {
+            animdata.just_finished = true;
+            if animdata.mode == AnimationMode::Loop {
+                animdata.index = 0;
+            }
+        } else {
+            animdata.index += 1;
+        }
+    }
+}
+
+//only shows whatever frame the simulation is on, runs every render frame
+pub fn animation_sprite_system(
     mut q_entities: Query<(
         &Handle<TextureAtlas>, 
         &mut TextureAtlasSprite, 
-        &mut AnimationData
-    )>, time: Res<Time>,
+        &AnimationData
+    )>,
     texture_atlases: Res<Assets<TextureAtlas>>,){
-    for (atlas, mut sprite, mut animdata) in &mut q_entities {
+    for (atlas, mut sprite, animdata) in &mut q_entities {
         if let Some(atlas) = texture_atlases.get(atlas) {
-            animdata.timer.tick(time.delta());
-            if animdata.timer.just_finished() {
-                animdata.index = if animdata.index >= animdata.anim_handles.len() - 1 {
-                    animdata.just_finished = true;
-                    0
-                } else {
-                    animdata.index + 1
-                };
-                if let Some(index) = atlas.get_texture_index(&animdata.anim_handles[animdata.index]) {
-                    sprite.index = index;
+            if let Some(handle) = animdata.anim_handles.get(animdata.index) {
+                if let Some(index) = atlas.get_texture_index(handle) {
+                    if sprite.index != index {
+                        sprite.index = index;
+                    }
                 }
             }
+            //anchors are authored for the unflipped sprite
+            let mut anchor = animdata.anchors.get(animdata.index).copied().unwrap_or(Vec2::ZERO);
+            if sprite.flip_x {
+                anchor.x = -anchor.x;
+            }
+            if sprite.anchor.as_vec() != anchor {
+                sprite.anchor = if anchor == Vec2::ZERO { Anchor::Center } else { Anchor::Custom(anchor) };
+            }
         }
     }
 }
\ No newline at end of file
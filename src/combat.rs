@@ -0,0 +1,516 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+
+use crate::{Rules, AttackKind, Projectile, Contact, Fixed, FixedVec2, pushbox_center, ActionComponent, FighterState, Health, Inputs, Movable, MovementData, Player, Stage, Throw};
+
+const KNOCKDOWN_FRAMES: i32 = 40;
+
+#[derive(Debug, Clone, Copy, Hash, Deserialize, Serialize, Reflect)]
+pub struct HitboxEffect {
+    pub offset: FixedVec2,
+    pub size: FixedVec2,
+    pub duration: i32,
+    pub damage: i32,
+    pub hitstun: i32,
+    pub blockstun: i32,
+    pub knockback: FixedVec2,
+    #[serde(default)]
+    pub knockdown: bool,
+    #[serde(default)]
+    pub attribute: HitAttribute,
+    #[serde(default)]
+    pub chip: i32,
+    //percent the whole combo is scaled by when this hit starts it
+    #[serde(default = "default_starter_scaling")]
+    pub starter_scaling: i32,
+    //juggle points this hit uses up on an airborne opponent
+    #[serde(default = "default_juggle_cost")]
+    pub juggle_cost: i32
+}
+
+fn default_starter_scaling() -> i32 {
+    100
+}
+
+fn default_juggle_cost() -> i32 {
+    1
+}
+
+impl Default for HitboxEffect {
+    fn default() -> Self {
+        HitboxEffect {
+            offset: FixedVec2::ZERO,
+            size: FixedVec2::ZERO,
+            duration: 0,
+            damage: 0,
+            hitstun: 0,
+            blockstun: 0,
+            knockback: FixedVec2::ZERO,
+            knockdown: false,
+            attribute: HitAttribute::default(),
+            chip: 0,
+            starter_scaling: default_starter_scaling(),
+            juggle_cost: default_juggle_cost()
+        }
+    }
+}
+
+//mids and highs can be blocked either way, highs just tend to whiff on crouchers because of hurtbox shapes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Hash, Deserialize, Serialize, Reflect)]
+pub enum HitAttribute {
+    #[default]
+    Mid,
+    High,
+    Low,
+    Overhead
+}
+
+impl HitAttribute {
+    pub fn blocked_by(&self, crouching: bool) -> bool {
+        match self {
+            HitAttribute::Mid | HitAttribute::High => true,
+            HitAttribute::Low => crouching,
+            HitAttribute::Overhead => !crouching
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Hash, Reflect)]
+pub struct ActiveHitbox {
+    pub hitbox: HitboxEffect,
+    pub frame: i32,
+    pub has_hit: bool
+}
+
+#[derive(Component, Default, Debug, Clone, Hash, Reflect)]
+#[reflect(Component)]
+pub struct Hitboxes {
+    pub boxes: Vec<ActiveHitbox>
+}
+
+#[derive(Debug, Default, Clone, Copy, Hash, Deserialize, Serialize, Reflect)]
+pub struct Hurtbox {
+    pub offset: FixedVec2,
+    pub size: FixedVec2
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Reflect)]
+pub struct HurtboxShapes {
+    pub stand: Vec<Hurtbox>,
+    pub crouch: Vec<Hurtbox>,
+    pub air: Vec<Hurtbox>
+}
+
+impl Default for HurtboxShapes {
+    fn default() -> Self {
+        HurtboxShapes {
+            stand: vec![Hurtbox { offset: FixedVec2::ZERO, size: FixedVec2::from_f32(30.0, 60.0) }],
+            crouch: vec![Hurtbox { offset: FixedVec2::from_f32(0.0, -10.0), size: FixedVec2::from_f32(30.0, 40.0) }],
+            air: vec![Hurtbox { offset: FixedVec2::from_f32(0.0, 5.0), size: FixedVec2::from_f32(30.0, 50.0) }]
+        }
+    }
+}
+
+//index picks one of the current shape's boxes, None applies to all of them
+#[derive(Debug, Default, Clone, Hash, Deserialize, Serialize, Reflect)]
+#[serde(default)]
+pub struct HurtboxModifier {
+    pub index: Option<usize>,
+    pub offset: FixedVec2,
+    pub size: FixedVec2,
+    pub extra: Option<Hurtbox>,
+    pub disable: bool,
+    pub duration: i32
+}
+
+#[derive(Debug, Default, Clone, Hash, Reflect)]
+pub struct ActiveHurtboxModifier {
+    pub modifier: HurtboxModifier,
+    pub frame: i32
+}
+
+#[derive(Component, Default, Debug, Clone, Hash, Reflect)]
+#[reflect(Component)]
+pub struct Hurtboxes {
+    pub boxes: Vec<Hurtbox>,
+    pub modifiers: Vec<ActiveHurtboxModifier>
+}
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct HitEvent {
+    pub attacker: Entity,
+    pub defender: Entity,
+    pub damage: i32,
+    pub hitstun: i32,
+    pub blockstun: i32,
+    pub knockback: FixedVec2,
+    pub knockdown: bool,
+    pub chip: i32,
+    pub starter_scaling: i32,
+    pub juggle_cost: i32,
+    pub blocked: bool
+}
+
+//tracked on whoever is getting comboed, and reset once they're out of stun
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
+pub struct Combo {
+    pub hits: u32,
+    pub damage: i32,
+    pub starter_scaling: i32,
+    pub juggle: i32
+}
+
+pub fn boxes_overlap(a_center: FixedVec2, a_size: FixedVec2, b_center: FixedVec2, b_size: FixedVec2) -> bool {
+    (a_center.x - b_center.x).abs() < (a_size.x + b_size.x).half() &&
+    (a_center.y - b_center.y).abs() < (a_size.y + b_size.y).half()
+}
+
+pub fn update_hurtboxes(mut entities: Query<(&Player, &Movable, &FighterState, &mut Hurtboxes)>) {
+    for (player, movable, state, mut hurtboxes) in &mut entities {
+        let shapes = &player.fighter.hurtboxes;
+        let mut boxes = match state {
+            //no hitting fighters that are already on the floor
+            FighterState::Knockdown(_) if movable.grounded => vec![],
+            FighterState::Crouching => shapes.crouch.to_owned(),
+            _ if !movable.grounded => shapes.air.to_owned(),
+            _ if movable.input.current().has(&Inputs::DOWN) => shapes.crouch.to_owned(),
+            _ => shapes.stand.to_owned()
+        };
+
+        let mut disabled = vec![false; boxes.len()];
+        let mut extras = vec![];
+        for active in &hurtboxes.modifiers {
+            let modifier = &active.modifier;
+            for (i, hurtbox) in boxes.iter_mut().enumerate() {
+                if modifier.index.is_some_and(|index| index != i) {
+                    continue;
+                }
+                hurtbox.offset += modifier.offset;
+                hurtbox.size = (hurtbox.size + modifier.size).max(FixedVec2::ZERO);
+                if modifier.disable {
+                    disabled[i] = true;
+                }
+            }
+            if let Some(extra) = modifier.extra {
+                extras.push(extra);
+            }
+        }
+        let mut disabled_iter = disabled.iter();
+        boxes.retain(|_| !*disabled_iter.next().unwrap());
+        boxes.append(&mut extras);
+        //hurtboxes are written facing right
+        for hurtbox in &mut boxes {
+            hurtbox.offset.x *= movable.facing.sign();
+        }
+        hurtboxes.boxes = boxes;
+
+        hurtboxes.modifiers.retain_mut(|active| {
+            active.frame += 1;
+            return active.frame < active.modifier.duration;
+        });
+    }
+}
+
+//returns whether the defender is holding back and was free to block the attack
+pub fn is_blocking(attribute: HitAttribute, movable: &Movable, state: &FighterState, actions: &ActionComponent) -> bool {
+    let can_block = match state {
+        FighterState::Standing | FighterState::Crouching | FighterState::Blockstun(_) => actions.actions.len() == 0,
+        _ => false
+    };
+    if !can_block || !movable.grounded {
+        return false;
+    }
+    let input = movable.input.current().relative_to(&movable.facing);
+    if input.has_dir(&1) {
+        return attribute.blocked_by(true);
+    }
+    if input.has_dir(&4) || input.has_dir(&7) {
+        return attribute.blocked_by(false);
+    }
+    return false;
+}
+
+pub fn hitbox_system(
+    mut attackers: Query<(Entity, &Movable, &mut Hitboxes, Option<&Projectile>, Option<&Player>)>,
+    defenders: Query<(Entity, &Movable, &Hurtboxes, Option<(&FighterState, &ActionComponent)>, Option<&Player>, Option<&Combo>)>,
+    rules: Res<Rules>,
+    mut hit_events: EventWriter<HitEvent>
+) {
+    //(attacking player, projectile id, defending player), fighters' own hits go before their projectiles'
+    let mut hits: Vec<((usize, Option<u32>, usize), HitEvent)> = vec![];
+    for (attacker, attacker_movable, mut hitboxes, projectile, attacker_player) in &mut attackers {
+        let attacker_position = attacker_movable.position;
+        let owner = attacker_player.map(|player| player.handle).or(projectile.map(|projectile| projectile.owner)).unwrap_or(usize::MAX);
+        for active in &mut hitboxes.boxes {
+            if active.has_hit {
+                continue;
+            }
+            let hitbox = active.hitbox;
+            for (defender, defender_movable, hurtboxes, fighter, player, combo) in &defenders {
+                if defender == attacker {
+                    continue;
+                }
+                //projectiles don't hit whoever threw them
+                if let (Some(projectile), Some(player)) = (projectile, player) {
+                    if projectile.owner == player.handle {
+                        continue;
+                    }
+                }
+                let kind = if projectile.is_some() { AttackKind::Projectile } else { AttackKind::Strike };
+                if fighter.is_some_and(|(_, actions)| actions.invulnerable_to(kind)) {
+                    continue;
+                }
+                //out of juggle points, the rest of the combo whiffs until they land
+                if !defender_movable.grounded && combo.is_some_and(|combo| combo.hits > 0 && combo.juggle + hitbox.juggle_cost > rules.0.juggle_limit) {
+                    continue;
+                }
+                let defender_position = defender_movable.position;
+                if hurtboxes.boxes.iter().any(|hurtbox| boxes_overlap(
+                    attacker_position + hitbox.offset, hitbox.size,
+                    defender_position + hurtbox.offset, hurtbox.size
+                )) {
+                    //knock the defender away from the attacker
+                    let direction = if defender_position.x < attacker_position.x { -Fixed::ONE } else { Fixed::ONE };
+                    let blocked = fighter.is_some_and(|(state, actions)| is_blocking(hitbox.attribute, defender_movable, state, actions));
+                    let key = (owner, projectile.map(|projectile| projectile.id), player.map_or(usize::MAX, |player| player.handle));
+                    hits.push((key, HitEvent {
+                        attacker,
+                        defender,
+                        damage: hitbox.damage,
+                        hitstun: hitbox.hitstun,
+                        blockstun: hitbox.blockstun,
+                        knockback: FixedVec2::new(hitbox.knockback.x * direction, hitbox.knockback.y),
+                        knockdown: hitbox.knockdown,
+                        chip: hitbox.chip,
+                        starter_scaling: hitbox.starter_scaling,
+                        juggle_cost: hitbox.juggle_cost,
+                        blocked
+                    }));
+                    active.has_hit = true;
+                    break;
+                }
+            }
+        }
+        hitboxes.boxes.retain_mut(|active| {
+            active.frame += 1;
+            return active.frame < active.hitbox.duration;
+        });
+    }
+
+    //query order changes when a rollback respawns projectiles, and apply_hits depends on the order
+    hits.sort_by_key(|(key, _)| *key);
+    for (_, hit) in hits {
+        hit_events.send(hit);
+    }
+}
+
+//whether the fighter is up against the wall it would get knocked towards
+fn is_cornered(movable: &Movable, player: &Player, direction: Fixed, stage: &Stage) -> bool {
+    let pushbox = &player.fighter.pushbox;
+    let center = pushbox_center(movable, pushbox);
+    if direction < Fixed::ZERO {
+        center.x - pushbox.size.x.half() <= stage.0.left_wall + Fixed::ONE
+    } else {
+        center.x + pushbox.size.x.half() >= stage.0.right_wall - Fixed::ONE
+    }
+}
+
+pub fn apply_hits(
+    mut hit_events: EventReader<HitEvent>,
+    mut movables: Query<&mut Movable>,
+    mut defenders: Query<(&mut Health, &mut FighterState, &mut ActionComponent, &mut Hitboxes, &mut Throw, Option<&mut Combo>)>,
+    players: Query<&Player>,
+    projectiles: Query<&Projectile>,
+    stage: Res<Stage>,
+    rules: Res<Rules>
+) {
+    let rules = &rules.0;
+    for hit in hit_events.iter() {
+        //a cornered defender can't be pushed any further, so the attacker gets pushed back instead
+        if hit.knockback.x != Fixed::ZERO && !projectiles.contains(hit.attacker) {
+            let cornered = match (players.get(hit.defender), movables.get(hit.defender)) {
+                (Ok(player), Ok(movable)) => is_cornered(movable, player, hit.knockback.x, &stage),
+                _ => false
+            };
+            if cornered {
+                if let Ok(mut movable) = movables.get_mut(hit.attacker) {
+                    let stun = if hit.blocked { hit.blockstun } else { hit.hitstun };
+                    movable.movements.push(MovementData::new(
+                        hit.knockback.x.abs(),
+                        stun.max(1),
+                        Fixed::ONE,
+                        0,
+                        FixedVec2::new(-hit.knockback.x.signum(), Fixed::ZERO)
+                    ));
+                }
+            }
+        }
+
+        if let Ok((_, _, mut attacker_actions, _, _, _)) = defenders.get_mut(hit.attacker) {
+            attacker_actions.contact = if hit.blocked { Contact::Block } else { Contact::Hit };
+            attacker_actions.victim = players.get(hit.defender).ok().map(|player| player.handle);
+        }
+
+        //the push lasts as long as the hitstun the defender actually got
+        let mut knockback_frames = hit.hitstun;
+        if let Ok((mut health, mut state, mut actions, mut hitboxes, mut throw, combo)) = defenders.get_mut(hit.defender) {
+            if hit.blocked {
+                health.current = (health.current - hit.chip).max(0);
+                debug!("Blocked! {} chip damage. {}/{}", hit.chip, health.current, health.max);
+                *state = FighterState::Blockstun(hit.blockstun);
+                if let Ok(mut movable) = movables.get_mut(hit.defender) {
+                    if hit.knockback.x != Fixed::ZERO {
+                        movable.movements.push(MovementData::new(
+                            hit.knockback.x.abs(),
+                            hit.blockstun.max(1),
+                            Fixed::ONE,
+                            0,
+                            FixedVec2::new(hit.knockback.x.signum(), Fixed::ZERO)
+                        ));
+                    }
+                }
+                continue;
+            }
+
+            //armor takes the damage but keeps the action going
+            if actions.actions.last_mut().is_some_and(|action| action.absorb_hit()) {
+                health.current = (health.current - hit.damage).max(0);
+                debug!("Armored {} damage! {}/{}", hit.damage, health.current, health.max);
+                continue;
+            }
+
+            let counter_hit = actions.actions.last().is_some_and(|action| action.counter_hittable());
+            let (mut damage, mut hitstun) = if counter_hit {
+                (hit.damage + hit.damage * rules.counter_hit_damage_percent / 100, hit.hitstun + rules.counter_hit_hitstun)
+            } else {
+                (hit.damage, hit.hitstun)
+            };
+            if let Some(mut combo) = combo {
+                if combo.hits == 0 {
+                    combo.starter_scaling = hit.starter_scaling;
+                }
+                let scaling = (rules.scaling(combo.hits) * combo.starter_scaling / 100).max(rules.minimum_scaling);
+                if damage > 0 {
+                    damage = (damage * scaling / 100).max(1);
+                }
+                hitstun = rules.combo_hitstun(hitstun, combo.hits);
+                let airborne = movables.get(hit.defender).map_or(false, |movable| !movable.grounded);
+                if airborne || hit.knockback.y > Fixed::ZERO {
+                    combo.juggle += hit.juggle_cost;
+                }
+                combo.hits += 1;
+                combo.damage += damage;
+            }
+            health.current = (health.current - damage).max(0);
+            if counter_hit {
+                debug!("Counter hit for {} damage! {}/{}", damage, health.current, health.max);
+            } else {
+                debug!("Hit for {} damage! {}/{}", damage, health.current, health.max);
+            }
+
+            //getting hit interrupts whatever the defender was doing
+            actions.actions.clear();
+            hitboxes.boxes.clear();
+            throw.grabs.clear();
+            *state = if hit.knockdown {
+                FighterState::Knockdown(KNOCKDOWN_FRAMES)
+            } else {
+                FighterState::Hitstun(hitstun)
+            };
+            knockback_frames = hitstun;
+        }
+        if let Ok(mut movable) = movables.get_mut(hit.defender) {
+            if hit.knockback.x != Fixed::ZERO {
+                movable.movements.push(MovementData::new(
+                    hit.knockback.x.abs(),
+                    knockback_frames.max(1),
+                    Fixed::ONE,
+                    0,
+                    FixedVec2::new(hit.knockback.x.signum(), Fixed::ZERO)
+                ));
+            }
+            if hit.knockback.y != Fixed::ZERO {
+                movable.yspeed = hit.knockback.y;
+            }
+        }
+    }
+}
+
+pub fn combo_system(mut players: Query<(&Player, &FighterState, &mut Combo)>) {
+    for (player, state, mut combo) in &mut players {
+        if combo.hits == 0 {
+            continue;
+        }
+        match state {
+            FighterState::Hitstun(_) | FighterState::Knockdown(_) | FighterState::Thrown(_) => {}
+            _ => {
+                if combo.hits > 1 {
+                    debug!("{} hit combo on player {} for {} damage!", combo.hits, player.handle + 1, combo.damage);
+                }
+                *combo = Combo { ..default() };
+            }
+        }
+    }
+}
+
+pub fn combo_display_system(mut contexts: EguiContexts, players: Query<(&Player, &Combo)>) {
+    for (player, combo) in &players {
+        if combo.hits < 2 {
+            continue;
+        }
+        //shown on the side of whoever is doing the combo
+        let anchor = if player.handle == 0 { egui::Align2::RIGHT_TOP } else { egui::Align2::LEFT_TOP };
+        egui::Area::new(format!("combo_{}", player.handle))
+            .anchor(anchor, egui::vec2(0.0, 40.0))
+            .show(contexts.ctx_mut(), |ui| {
+                ui.heading(format!("{} Hits", combo.hits));
+                ui.label(format!("{} damage", combo.damage));
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fighter_state_system, spawn_test_fighter, Fighter};
+
+    #[test]
+    fn self_gatling_loop_ends() {
+        let mut app = App::new();
+        app.add_event::<HitEvent>();
+        app.init_resource::<Stage>();
+        app.init_resource::<Rules>();
+        app.add_systems(Update, (apply_hits, combo_system, fighter_state_system).chain());
+        let attacker = spawn_test_fighter(&mut app.world, 0, Fighter::default());
+        let defender = spawn_test_fighter(&mut app.world, 1, Fighter::default());
+        //a grounded 3 frame jab that cancels into itself on hit, it keeps landing for as long as the defender is stuck
+        let frames_between_hits = 3;
+        let mut hits = 0;
+        for frame in 0..1000 {
+            if frame % frames_between_hits == 0 {
+                if hits > 0 && !app.world.get::<FighterState>(defender).unwrap().in_stun() {
+                    break;
+                }
+                app.world.resource_mut::<Events<HitEvent>>().send(HitEvent {
+                    attacker,
+                    defender,
+                    damage: 30,
+                    hitstun: 12,
+                    blockstun: 9,
+                    knockback: FixedVec2::ZERO,
+                    knockdown: false,
+                    chip: 0,
+                    starter_scaling: 100,
+                    juggle_cost: 1,
+                    blocked: false
+                });
+                hits += 1;
+            }
+            app.update();
+        }
+        assert!(!app.world.get::<FighterState>(defender).unwrap().in_stun());
+        assert!(hits < 20, "the loop kept going for {} hits", hits);
+    }
+}
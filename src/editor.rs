@@ -1,6 +1,6 @@
 use std::{fs::{self, File}, io::Write};
 
-use crate::{get_default_fighter, AnimationData, Effect, FighterList, Inputs, Player, SpriteRes};
+use crate::{get_default_fighter, AnimationData, AnimationMode, Armor, CancelWindow, Charge, CounterHit, Effect, Invulnerability, MoveCategory, Fixed, FighterList, HitAttribute, Inputs, Player, SpriteRes};
 
 use bevy_egui::{egui::{self, load::SizedTexture, Pos2, TextureId, TextureOptions, Vec2}, render_systems::EguiTextureId, EguiContexts};
 use bevy::{prelude::*, ui};
@@ -163,10 +163,71 @@ pub fn editor_system(mut contexts: EguiContexts,
                             attack.input |= Inputs::RIGHT;
                         }
                     });
+                    egui::ComboBox::from_label("Category")
+                        .selected_text(format!("{:?}", attack.category))
+                        .show_ui(ui, |ui| {
+                            for category in [MoveCategory::Movement, MoveCategory::Jump, MoveCategory::Normal, MoveCategory::Special, MoveCategory::Super] {
+                                ui.selectable_value(&mut attack.category, category, format!("{:?}", category));
+                            }
+                        }
+                    );
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut attack.from_idle, "From Idle");
+                        ui.checkbox(&mut attack.aerial, "Air Only");
+                        ui.checkbox(&mut attack.aerial_action, "Also In Air");
+                    });
+                    ui.horizontal(|ui| {
+                        let mut has_priority = attack.priority.is_some();
+                        if ui.checkbox(&mut has_priority, "Priority Override").changed() {
+                            attack.priority = if has_priority { Some(0) } else { None };
+                        }
+                        if let Some(priority) = &mut attack.priority {
+                            ui.add(egui::Slider::new(priority, -10..=10));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        let mut has_window = attack.motion_window.is_some();
+                        if ui.checkbox(&mut has_window, "Motion Window").changed() {
+                            attack.motion_window = if has_window { Some(30) } else { None };
+                        }
+                        if let Some(motion_window) = &mut attack.motion_window {
+                            ui.add(egui::Slider::new(motion_window, 1..=60));
+                        }
+                        let mut has_gap = attack.motion_gap.is_some();
+                        if ui.checkbox(&mut has_gap, "Motion Gap").changed() {
+                            attack.motion_gap = if has_gap { Some(15) } else { None };
+                        }
+                        if let Some(motion_gap) = &mut attack.motion_gap {
+                            ui.add(egui::Slider::new(motion_gap, 1..=30));
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Slider::new(&mut attack.button_window, 1..=10).text("Button Window"));
+                        ui.checkbox(&mut attack.negative_edge, "Negative Edge");
+                    });
+                    ui.horizontal(|ui| {
+                        let mut has_charge = attack.charge.is_some();
+                        if ui.checkbox(&mut has_charge, "Charge").changed() {
+                            attack.charge = if has_charge { Some(Charge { direction: 4, frames: 45, retention: 8 }) } else { None };
+                        }
+                        if let Some(charge) = &mut attack.charge {
+                            ui.add(egui::Slider::new(&mut charge.direction, 1..=9).text("Direction"));
+                            ui.add(egui::Slider::new(&mut charge.frames, 1..=120).text("Frames"));
+                            ui.add(egui::Slider::new(&mut charge.retention, 0..=30).text("Retention"));
+                        }
+                    });
                     ui.collapsing(format!("{} Actions:", attack.name), |ui| { 
                         for action in &mut attack.actions {
                             ui.label(format!("Action:"));
                             ui.add(egui::Slider::new(&mut action.animation_speed, 0.0..=5.0).text("Animation Speed"));
+                            egui::ComboBox::from_label("Animation Mode")
+                                .selected_text(format!("{:?}", action.animation_mode))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut action.animation_mode, AnimationMode::Loop, "Loop");
+                                    ui.selectable_value(&mut action.animation_mode, AnimationMode::Hold, "Hold");
+                                }
+                            );
+                            ui.checkbox(&mut action.restart_animation, "Restart Animation");
                             egui::ComboBox::from_label("Sprite")
                                 .selected_text(format!("{:?}", action.sprite))
                                 .show_ui(ui, |ui| {
@@ -178,6 +239,52 @@ pub fn editor_system(mut contexts: EguiContexts,
                                 }
                             );
                             ui.add(egui::Slider::new(&mut action.duration, 0..=30).text("Duration"));
+                            ui.label(format!("Cancels:"));
+                            for window in &mut action.cancels {
+                                ui.add(egui::Slider::new(&mut window.start, 1..=60).text("Start Frame"));
+                                ui.add(egui::Slider::new(&mut window.end, 1..=60).text("End Frame"));
+                                ui.label(format!("Into: {:?} {:?}", window.categories, window.moves));
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut window.on_hit, "On Hit");
+                                    ui.checkbox(&mut window.on_block, "On Block");
+                                    ui.checkbox(&mut window.on_whiff, "On Whiff");
+                                });
+                            }
+                            if ui.button("Add Cancel").clicked() {
+                                action.cancels.push(CancelWindow::default());
+                            }
+                            ui.label(format!("Invulnerability:"));
+                            for window in &mut action.invulnerability {
+                                ui.add(egui::Slider::new(&mut window.start, 1..=60).text("Start Frame"));
+                                ui.add(egui::Slider::new(&mut window.end, 1..=60).text("End Frame"));
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut window.strike, "Strike");
+                                    ui.checkbox(&mut window.throw, "Throw");
+                                    ui.checkbox(&mut window.projectile, "Projectile");
+                                });
+                            }
+                            if ui.button("Add Invulnerability").clicked() {
+                                action.invulnerability.push(Invulnerability { start: 1, end: 1, ..default() });
+                            }
+                            let mut has_armor = action.armor.is_some();
+                            ui.checkbox(&mut has_armor, "Armor");
+                            if has_armor {
+                                let armor = action.armor.get_or_insert(Armor { start: 1, end: 1, hits: 1 });
+                                ui.add(egui::Slider::new(&mut armor.start, 1..=60).text("Armor Start"));
+                                ui.add(egui::Slider::new(&mut armor.end, 1..=60).text("Armor End"));
+                                ui.add(egui::Slider::new(&mut armor.hits, 1..=5).text("Armor Hits"));
+                            } else {
+                                action.armor = None;
+                            }
+                            let mut has_counter_hit = action.counter_hit.is_some();
+                            ui.checkbox(&mut has_counter_hit, "Counter Hit State");
+                            if has_counter_hit {
+                                let counter_hit = action.counter_hit.get_or_insert(CounterHit { start: 1, end: 1 });
+                                ui.add(egui::Slider::new(&mut counter_hit.start, 1..=60).text("Counter Hit Start"));
+                                ui.add(egui::Slider::new(&mut counter_hit.end, 1..=60).text("Counter Hit End"));
+                            } else {
+                                action.counter_hit = None;
+                            }
                             ui.label(format!("Start Effects:"));
                             for effect in &mut action.start_effects {
                                 effect_ui(ui, effect);
@@ -210,23 +317,128 @@ fn effect_ui(ui: &mut egui::Ui, effect: &mut Effect) {
     match effect {
         Effect::Move(val) => {
             ui.label("Movement Effect:");
-            ui.add(egui::Slider::new(&mut val.distance, 0.0..=((val.duration as f32) * 10.0)).text("Distance"));
+            fixed_slider(ui, &mut val.distance, 0.0..=((val.duration as f32) * 10.0), "Distance");
             ui.add(egui::Slider::new(&mut val.duration, 0..=30).text("Duration"));
-            ui.add(egui::Slider::new(&mut val.ease, -2.0..=2.0).text("Ease"));
-            ui.add(egui::Slider::new(&mut val.direction.x, -1.0..=1.0).text("Direction X"));
-            ui.add(egui::Slider::new(&mut val.direction.y, -1.0..=1.0).text("Direction Y"));
+            fixed_slider(ui, &mut val.ease, -2.0..=2.0, "Ease");
+            fixed_slider(ui, &mut val.direction.x, -1.0..=1.0, "Direction X");
+            fixed_slider(ui, &mut val.direction.y, -1.0..=1.0, "Direction Y");
         }
         Effect::Wait(val) => {
             ui.add(egui::Slider::new(val, 0..=30).text("Wait"));
         }
         Effect::SetYSpeed(val) => {
-            ui.add(egui::Slider::new(val, -15.0..=15.0).text("Set Y Speed"));
+            fixed_slider(ui, val, -15.0..=15.0, "Set Y Speed");
         }
         Effect::AddYSpeed(val) => {
-            ui.add(egui::Slider::new(val, -15.0..=15.0).text("Add Y Speed"));
+            fixed_slider(ui, val, -15.0..=15.0, "Add Y Speed");
+        }
+        Effect::Hitbox(val) => {
+            ui.label("Hitbox Effect:");
+            fixed_slider(ui, &mut val.offset.x, -100.0..=100.0, "Offset X");
+            fixed_slider(ui, &mut val.offset.y, -100.0..=100.0, "Offset Y");
+            fixed_slider(ui, &mut val.size.x, 0.0..=200.0, "Width");
+            fixed_slider(ui, &mut val.size.y, 0.0..=200.0, "Height");
+            ui.add(egui::Slider::new(&mut val.duration, 0..=30).text("Active Frames"));
+            ui.add(egui::Slider::new(&mut val.damage, 0..=200).text("Damage"));
+            ui.add(egui::Slider::new(&mut val.hitstun, 0..=60).text("Hitstun"));
+            ui.add(egui::Slider::new(&mut val.blockstun, 0..=60).text("Blockstun"));
+            fixed_slider(ui, &mut val.knockback.x, -100.0..=100.0, "Knockback X");
+            fixed_slider(ui, &mut val.knockback.y, -15.0..=15.0, "Knockback Y");
+            ui.checkbox(&mut val.knockdown, "Knockdown");
+            ui.add(egui::Slider::new(&mut val.chip, 0..=50).text("Chip Damage"));
+            ui.add(egui::Slider::new(&mut val.starter_scaling, 10..=100).text("Starter Scaling %"));
+            ui.add(egui::Slider::new(&mut val.juggle_cost, 0..=10).text("Juggle Cost"));
+            egui::ComboBox::from_label("Attribute")
+                .selected_text(format!("{:?}", val.attribute))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut val.attribute, HitAttribute::Mid, "Mid");
+                    ui.selectable_value(&mut val.attribute, HitAttribute::High, "High");
+                    ui.selectable_value(&mut val.attribute, HitAttribute::Low, "Low");
+                    ui.selectable_value(&mut val.attribute, HitAttribute::Overhead, "Overhead");
+                }
+            );
+        }
+        Effect::SetSprite(val) => {
+            ui.label("Set Sprite Effect:");
+            ui.text_edit_singleline(&mut val.sprite);
+            let mut start_later = val.frame.is_some();
+            ui.checkbox(&mut start_later, "Starting Frame");
+            if start_later {
+                let mut frame = val.frame.unwrap_or(0);
+                ui.add(egui::Slider::new(&mut frame, 0..=30).text("Frame"));
+                val.frame = Some(frame);
+            } else {
+                val.frame = None;
+            }
+            ui.add(egui::Slider::new(&mut val.speed, 0.0..=5.0).text("Animation Speed"));
+            egui::ComboBox::from_label("Animation Mode")
+                .selected_text(format!("{:?}", val.mode))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut val.mode, AnimationMode::Loop, "Loop");
+                    ui.selectable_value(&mut val.mode, AnimationMode::Hold, "Hold");
+                }
+            );
+        }
+        Effect::CallMoveIfDirectionHeld(val) | Effect::CallMoveIfDirectionPressed(val) => {
+            ui.label("Call Move If Direction:");
+            ui.text_edit_singleline(&mut val.move_name);
+            ui.add(egui::Slider::new(&mut val.direction, 1..=9).text("Direction"));
+        }
+        Effect::CallMoveIfButtonHeld(val) | Effect::CallMoveIfButtonPressed(val) | Effect::CallMoveIfButtonReleased(val) => {
+            ui.label("Call Move If Button:");
+            ui.text_edit_singleline(&mut val.move_name);
+            egui::ComboBox::from_label("Button")
+                .selected_text(format!("{:?}", val.button))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut val.button, Inputs::L, "L");
+                    ui.selectable_value(&mut val.button, Inputs::M, "M");
+                    ui.selectable_value(&mut val.button, Inputs::H, "H");
+                    ui.selectable_value(&mut val.button, Inputs::S, "S");
+                }
+            );
+        }
+        Effect::SpawnProjectile(val) => {
+            ui.label("Spawn Projectile:");
+            ui.text_edit_singleline(val);
+        }
+        Effect::Grab(val) => {
+            ui.label("Grab Effect:");
+            fixed_slider(ui, &mut val.offset.x, -100.0..=100.0, "Offset X");
+            fixed_slider(ui, &mut val.offset.y, -100.0..=100.0, "Offset Y");
+            fixed_slider(ui, &mut val.size.x, 0.0..=200.0, "Width");
+            fixed_slider(ui, &mut val.size.y, 0.0..=200.0, "Height");
+            ui.add(egui::Slider::new(&mut val.duration, 0..=30).text("Active Frames"));
+            ui.text_edit_singleline(&mut val.throw_move);
+            ui.add(egui::Slider::new(&mut val.tech_window, 0..=30).text("Tech Window"));
+        }
+        Effect::SetVictimOffset(val) => {
+            ui.label("Set Victim Offset:");
+            fixed_slider(ui, &mut val.x, -100.0..=100.0, "Offset X");
+            fixed_slider(ui, &mut val.y, -100.0..=100.0, "Offset Y");
+        }
+        Effect::Knockdown(val) => {
+            ui.add(egui::Slider::new(val, 0..=120).text("Knockdown"));
+        }
+        Effect::ModifyHurtbox(val) => {
+            ui.label("Modify Hurtbox Effect:");
+            fixed_slider(ui, &mut val.offset.x, -100.0..=100.0, "Offset X");
+            fixed_slider(ui, &mut val.offset.y, -100.0..=100.0, "Offset Y");
+            fixed_slider(ui, &mut val.size.x, -100.0..=100.0, "Width Change");
+            fixed_slider(ui, &mut val.size.y, -100.0..=100.0, "Height Change");
+            ui.checkbox(&mut val.disable, "Disable");
+            ui.add(egui::Slider::new(&mut val.duration, 0..=30).text("Duration"));
         }
         _ => {
             ui.label(format!("{:?}", effect));
         }
     }
-}
\ No newline at end of file
+}
+//the sim stores fixed point numbers, the sliders edit them as floats
+fn fixed_slider(ui: &mut egui::Ui, value: &mut Fixed, range: std::ops::RangeInclusive<f32>, text: &str) {
+    ui.add(egui::Slider::from_get_set(*range.start() as f64..=*range.end() as f64, |new_value| {
+        if let Some(new_value) = new_value {
+            *value = Fixed::from_f32(new_value as f32);
+        }
+        return value.to_f32() as f64;
+    }).text(text));
+}
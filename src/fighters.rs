@@ -1,26 +1,174 @@
-use bevy::{prelude::*, utils::{HashMap, BoxedFuture}, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}};
+use bevy::{prelude::*, utils::{HashMap, BoxedFuture}, reflect::TypeUuid, asset::{AssetLoader, LoadContext, LoadedAsset}};
 use serde::{Deserialize, Serialize};
 
-use crate::{actions::{Action, Effect, MovementEffect}, Inputs};
+use crate::{actions::{default_true, Action, CounterHit, Effect, Invulnerability, MovementEffect}, combat::HurtboxShapes, projectile::ProjectileInfo, Fixed, FixedVec2, Inputs, Pushbox};
 
-#[derive(Debug, Deserialize, Serialize, TypeUuid, TypePath, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, TypeUuid, Reflect, Clone)]
 #[uuid = "0b336136-5f0c-491b-9d9a-2f7405b002c5"]
 pub struct Fighter {
     pub name: String,
-    pub moves: Vec<Move>
+    #[serde(default = "default_health")]
+    pub health: i32,
+    #[serde(default)]
+    pub hurtboxes: HurtboxShapes,
+    #[serde(default)]
+    pub pushbox: Pushbox,
+    #[serde(default = "default_landing_recovery")]
+    pub landing_recovery: i32,
+    //pressing this while being thrown breaks the throw
+    #[serde(default = "default_throw_input")]
+    pub throw_input: Inputs,
+    pub moves: Vec<Move>,
+    #[serde(default)]
+    pub projectiles: Vec<ProjectileInfo>
+}
+
+fn default_health() -> i32 {
+    1000
+}
+
+fn default_landing_recovery() -> i32 {
+    3
+}
+
+fn default_throw_input() -> Inputs {
+    Inputs::H
 }
 
 #[derive(Resource)]
 pub struct FighterList(pub HashMap<String, Fighter>);
 
-#[derive(Default, Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Reflect)]
 pub struct Move {
     pub name: String,
+    #[serde(default)]
+    pub category: MoveCategory,
+    //false means the move can only be reached by cancelling another action into it
+    #[serde(default = "default_true")]
+    pub from_idle: bool,
+    //air only
+    #[serde(default)]
+    pub aerial: bool,
+    //usable in the air as well as on the ground
+    #[serde(default)]
+    pub aerial_action: bool,
+    //frames apart the buttons of a multi-button move can be pressed in, 1 means they have to be on the same frame
+    #[serde(default = "default_button_window")]
+    pub button_window: i32,
+    //also comes out when its buttons are released
+    #[serde(default)]
+    pub negative_edge: bool,
+    //override the input config's motion_window and motion_gap for this move
+    #[serde(default)]
+    pub motion_window: Option<usize>,
+    #[serde(default)]
+    pub motion_gap: Option<usize>,
+    //direction to hold before the motion, [4]6 is a charge of 4 with a motion of [6]
+    #[serde(default)]
+    pub charge: Option<Charge>,
+    //overrides the usual priority rules when several moves match on the same frame, higher wins
+    #[serde(default)]
+    pub priority: Option<i32>,
     pub motion: Vec<u8>,
     pub input: Inputs,
     pub actions: Vec<Action>
 }
 
+fn default_button_window() -> i32 {
+    3
+}
+
+//same defaults as the serde ones, so moves built in code only need their own fields
+impl Default for Move {
+    fn default() -> Self {
+        Move {
+            name: String::new(),
+            category: MoveCategory::default(),
+            from_idle: true,
+            aerial: false,
+            aerial_action: false,
+            button_window: default_button_window(),
+            negative_edge: false,
+            motion_window: None,
+            motion_gap: None,
+            charge: None,
+            priority: None,
+            motion: vec![],
+            input: Inputs::NONE,
+            actions: vec![]
+        }
+    }
+}
+
+//charge keeps building through other moves and stun, but one charge can't be split between two moves
+#[derive(Default, Debug, Deserialize, Serialize, Clone, Copy, Reflect)]
+pub struct Charge {
+    pub direction: u8,
+    pub frames: i32,
+    //how long the charge lasts after letting go of the direction
+    #[serde(default = "default_charge_retention")]
+    pub retention: i32
+}
+
+fn default_charge_retention() -> i32 {
+    8
+}
+
+impl Move {
+    pub fn usable(&self, grounded: bool) -> bool {
+        if grounded {
+            return !self.aerial;
+        }
+        return self.aerial || self.aerial_action;
+    }
+
+    //a pressed button beats a move without one (so 6+L isn't a walk step), then longer motions beat shorter ones,
+    //then more buttons beat fewer, then the higher category wins
+    pub fn priority_key(&self) -> (i32, bool, usize, u32, u8) {
+        let buttons = self.input.button_input().bits().count_ones();
+        return (self.priority.unwrap_or(0), buttons > 0, self.motion.len() + self.charge.is_some() as usize, buttons, self.category.rank());
+    }
+    //same motion with more buttons, like DP into Spec DP
+    pub fn upgrades_to(&self, other: &Move) -> bool {
+        let buttons = self.input.button_input();
+        let other_buttons = other.input.button_input();
+        return self.motion == other.motion
+            && self.input.movement_input() == other.input.movement_input()
+            && other_buttons != buttons
+            && other_buttons.has(&buttons);
+    }
+}
+
+#[derive(Default, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Reflect)]
+pub enum MoveCategory {
+    #[default]
+    Movement,
+    Jump,
+    Normal,
+    Special,
+    Super
+}
+
+impl MoveCategory {
+    pub fn rank(&self) -> u8 {
+        match self {
+            MoveCategory::Movement => 0,
+            MoveCategory::Jump => 1,
+            MoveCategory::Normal => 2,
+            MoveCategory::Special => 3,
+            MoveCategory::Super => 4
+        }
+    }
+    //normal -> special -> super, movement and jumps only cancel into their own category
+    pub fn cancels_into(&self, other: &MoveCategory) -> bool {
+        match self {
+            MoveCategory::Normal => matches!(other, MoveCategory::Normal | MoveCategory::Special | MoveCategory::Super),
+            MoveCategory::Special => matches!(other, MoveCategory::Special | MoveCategory::Super),
+            _ => self == other
+        }
+    }
+}
+
 pub fn get_fighter(character: String, fighter_list: &Res<FighterList>) -> Fighter {
     let fighter = &character.to_lowercase();
     if fighter_list.0.contains_key(fighter) {
@@ -33,9 +181,15 @@ pub fn get_fighter(character: String, fighter_list: &Res<FighterList>) -> Fighte
 pub fn get_default_fighter(character: String) -> Fighter {
     Fighter {
         name: String::from(character),
+        health: default_health(),
+        hurtboxes: HurtboxShapes::default(),
+        pushbox: Pushbox::default(),
+        landing_recovery: default_landing_recovery(),
+        throw_input: default_throw_input(),
         moves: vec![
             Move {
                 name: String::from("Dash"),
+                category: MoveCategory::Movement,
                 motion: vec![5, 6, 5, 6],
                 input: Inputs::RIGHT,
                 actions: vec![
@@ -43,34 +197,45 @@ pub fn get_default_fighter(character: String) -> Fighter {
                         Effect::Move(
                             MovementEffect{
                                 duration: 100, 
-                                distance: 1.0, 
-                                ease: 1.0, 
-                                direction: Vec2::new(1.0, 0.0)
+                                distance: Fixed::ONE, 
+                                ease: Fixed::ONE, 
+                                direction: FixedVec2::new(Fixed::ONE, Fixed::ZERO)
                             }
                         ), 
                         Effect::Wait(100)
                         ], ..Default::default()}
-                    ]
+                    ],
+                ..Default::default()
             },
             Move {
                 name: String::from("DP"),
+                category: MoveCategory::Special,
                 motion: vec![6, 2, 3],
                 input: Inputs::H,
-                actions: vec![]
+                actions: vec![
+                    //10 frames of startup, only the ones after the invulnerability can be counter hit
+                    Action {sprite: String::from("Idle"), effects: vec![Effect::Wait(20)], invulnerability: vec![
+                        Invulnerability { start: 1, end: 8, strike: true, throw: true, projectile: false }
+                        ], counter_hit: Some(CounterHit { start: 9, end: 10 }), ..Default::default()}
+                    ],
+                ..Default::default()
             },
             Move {
                 name: String::from("Spec DP"),
+                category: MoveCategory::Special,
                 motion: vec![6, 2, 3],
                 input: Inputs::H | Inputs::S,
-                actions: vec![]
+                ..Default::default()
             },
             Move {
                 name: String::from("Super"),
+                category: MoveCategory::Super,
                 motion: vec![6, 2, 4, 6],
                 input: Inputs::S,
-                actions: vec![]
+                ..Default::default()
             }
-        ]
+        ],
+        projectiles: vec![]
     }
 }
 
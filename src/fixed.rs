@@ -0,0 +1,279 @@
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+//16.16 fixed point number for everything the simulation touches, floats only show up when loading data and rendering
+//integer math gives the same result on every CPU, which rollback needs to stay in sync
+const FRAC_BITS: u32 = 16;
+const LOG_BITS: u32 = 24;
+
+//2^(2^-k) for k = 1..=16 in 2.30 fixed point, used to build up 2^x one fractional bit at a time
+const EXP2_TABLE: [i64; 16] = [
+    1518500250, 1276901417, 1170923762, 1121280436,
+    1097253708, 1085434106, 1079572136, 1076653033,
+    1075196443, 1074468888, 1074105294, 1073923544,
+    1073832680, 1073787251, 1073764537, 1073753181,
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
+pub struct Fixed(pub i32);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+    pub const ONE: Fixed = Fixed(1 << FRAC_BITS);
+
+    pub fn from_int(value: i32) -> Fixed {
+        Fixed(value << FRAC_BITS)
+    }
+    pub fn from_f32(value: f32) -> Fixed {
+        Fixed((value * (1 << FRAC_BITS) as f32).round() as i32)
+    }
+    pub fn to_f32(self) -> f32 {
+        self.0 as f32 / (1 << FRAC_BITS) as f32
+    }
+    pub fn abs(self) -> Fixed {
+        Fixed(self.0.abs())
+    }
+    pub fn signum(self) -> Fixed {
+        Fixed::from_int(self.0.signum())
+    }
+    pub fn min(self, other: Fixed) -> Fixed {
+        Fixed(self.0.min(other.0))
+    }
+    pub fn max(self, other: Fixed) -> Fixed {
+        Fixed(self.0.max(other.0))
+    }
+    pub fn half(self) -> Fixed {
+        Fixed(self.0 >> 1)
+    }
+
+    //only meant for the easing curves, so it handles bases from 0 to 1 (anything below 0 is treated as 0)
+    pub fn powf(self, exponent: Fixed) -> Fixed {
+        if exponent == Fixed::ZERO {
+            return Fixed::ONE;
+        }
+        if self <= Fixed::ZERO {
+            return Fixed::ZERO;
+        }
+        if exponent == Fixed::ONE {
+            return self;
+        }
+        let log = (log2_q24(self.0 as i64) * exponent.0 as i64) >> FRAC_BITS;
+        Fixed(exp2_q24(log).clamp(i32::MIN as i64, i32::MAX as i64) as i32)
+    }
+}
+
+//log2 of a positive 16.16 value, returned with LOG_BITS fractional bits
+fn log2_q24(raw: i64) -> i64 {
+    let one: i64 = 1 << 30;
+    let mut value = raw << (30 - FRAC_BITS);
+    let mut int_part: i64 = 0;
+    while value >= 2 * one {
+        value >>= 1;
+        int_part += 1;
+    }
+    while value < one {
+        value <<= 1;
+        int_part -= 1;
+    }
+    let mut frac_part: i64 = 0;
+    for bit in (0..LOG_BITS).rev() {
+        value = (value * value) >> 30;
+        if value >= 2 * one {
+            value >>= 1;
+            frac_part |= 1 << bit;
+        }
+    }
+    (int_part << LOG_BITS) + frac_part
+}
+
+//2^x for x with LOG_BITS fractional bits, returned as a 16.16 value
+fn exp2_q24(x: i64) -> i64 {
+    let int_part = (x >> LOG_BITS).min(14);
+    let frac_part = x - ((x >> LOG_BITS) << LOG_BITS);
+    let mut result: i64 = 1 << 30;
+    for (k, factor) in EXP2_TABLE.iter().enumerate() {
+        if frac_part & (1 << (LOG_BITS - 1 - k as u32)) != 0 {
+            result = (result * factor) >> 30;
+        }
+    }
+    let shift = (30 - FRAC_BITS) as i64 - int_part;
+    if shift >= 62 {
+        0
+    } else if shift >= 0 {
+        result >> shift
+    } else {
+        result << -shift
+    }
+}
+
+impl Add for Fixed {
+    type Output = Fixed;
+    fn add(self, other: Fixed) -> Fixed {
+        Fixed(self.0 + other.0)
+    }
+}
+
+impl Sub for Fixed {
+    type Output = Fixed;
+    fn sub(self, other: Fixed) -> Fixed {
+        Fixed(self.0 - other.0)
+    }
+}
+
+impl Mul for Fixed {
+    type Output = Fixed;
+    fn mul(self, other: Fixed) -> Fixed {
+        Fixed(((self.0 as i64 * other.0 as i64) >> FRAC_BITS) as i32)
+    }
+}
+
+impl Div for Fixed {
+    type Output = Fixed;
+    fn div(self, other: Fixed) -> Fixed {
+        Fixed((((self.0 as i64) << FRAC_BITS) / other.0 as i64) as i32)
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Fixed;
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl AddAssign for Fixed {
+    fn add_assign(&mut self, other: Fixed) {
+        self.0 += other.0;
+    }
+}
+
+impl SubAssign for Fixed {
+    fn sub_assign(&mut self, other: Fixed) {
+        self.0 -= other.0;
+    }
+}
+
+impl MulAssign for Fixed {
+    fn mul_assign(&mut self, other: Fixed) {
+        *self = *self * other;
+    }
+}
+
+//data files keep writing plain floats, they get converted once on load
+impl Serialize for Fixed {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f32(self.to_f32())
+    }
+}
+
+impl<'de> Deserialize<'de> for Fixed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Fixed, D::Error> {
+        f32::deserialize(deserializer).map(Fixed::from_f32)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+pub struct FixedVec2 {
+    pub x: Fixed,
+    pub y: Fixed
+}
+
+impl FixedVec2 {
+    pub const ZERO: FixedVec2 = FixedVec2 { x: Fixed::ZERO, y: Fixed::ZERO };
+
+    pub fn new(x: Fixed, y: Fixed) -> FixedVec2 {
+        FixedVec2 { x, y }
+    }
+    pub fn from_f32(x: f32, y: f32) -> FixedVec2 {
+        FixedVec2 { x: Fixed::from_f32(x), y: Fixed::from_f32(y) }
+    }
+    pub fn from_vec2(value: Vec2) -> FixedVec2 {
+        FixedVec2::from_f32(value.x, value.y)
+    }
+    pub fn to_vec2(self) -> Vec2 {
+        Vec2::new(self.x.to_f32(), self.y.to_f32())
+    }
+    pub fn max(self, other: FixedVec2) -> FixedVec2 {
+        FixedVec2 { x: self.x.max(other.x), y: self.y.max(other.y) }
+    }
+}
+
+impl Add for FixedVec2 {
+    type Output = FixedVec2;
+    fn add(self, other: FixedVec2) -> FixedVec2 {
+        FixedVec2 { x: self.x + other.x, y: self.y + other.y }
+    }
+}
+
+impl Sub for FixedVec2 {
+    type Output = FixedVec2;
+    fn sub(self, other: FixedVec2) -> FixedVec2 {
+        FixedVec2 { x: self.x - other.x, y: self.y - other.y }
+    }
+}
+
+impl Mul<Fixed> for FixedVec2 {
+    type Output = FixedVec2;
+    fn mul(self, scale: Fixed) -> FixedVec2 {
+        FixedVec2 { x: self.x * scale, y: self.y * scale }
+    }
+}
+
+impl AddAssign for FixedVec2 {
+    fn add_assign(&mut self, other: FixedVec2) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
+//written as an (x, y) tuple, same as the Vec2s the data files used before
+impl Serialize for FixedVec2 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (self.x, self.y).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FixedVec2 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FixedVec2, D::Error> {
+        <(Fixed, Fixed)>::deserialize(deserializer).map(|(x, y)| FixedVec2 { x, y })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MovementData;
+
+    #[test]
+    fn powf_known_values() {
+        assert_eq!(Fixed::from_f32(0.5).powf(Fixed::from_int(2)), Fixed::from_f32(0.25));
+        assert_eq!(Fixed::from_f32(0.25).powf(Fixed::from_f32(0.5)), Fixed::from_f32(0.5));
+        assert_eq!(Fixed::ONE.powf(Fixed::from_f32(std::f32::consts::E)), Fixed::ONE);
+        let approximate = Fixed::from_f32(0.3).powf(Fixed::from_f32(1.7)).to_f32();
+        assert!((approximate - 0.3f32.powf(1.7)).abs() < 0.001, "0.3^1.7 came out as {}", approximate);
+    }
+
+    #[test]
+    fn powf_edge_cases() {
+        assert_eq!(Fixed::ZERO.powf(Fixed::from_int(2)), Fixed::ZERO);
+        assert_eq!(Fixed::from_int(-1).powf(Fixed::from_f32(0.5)), Fixed::ZERO);
+        assert_eq!(Fixed::from_f32(0.7).powf(Fixed::ZERO), Fixed::ONE);
+        assert_eq!(Fixed::ZERO.powf(Fixed::ZERO), Fixed::ONE);
+    }
+
+    #[test]
+    fn movement_adds_up_to_distance() {
+        for ease in [0.5, 1.0, 2.0, 3.7] {
+            for (distance, duration) in [(Fixed::from_int(100), 10), (Fixed::from_f32(1.5), 1), (Fixed::from_f32(37.3), 7)] {
+                let mut movement = MovementData::new(distance, duration, Fixed::from_f32(ease), 0, FixedVec2::new(Fixed::ONE, Fixed::ZERO));
+                let mut travelled = Fixed::ZERO;
+                while !movement.finished() {
+                    travelled += movement.step();
+                }
+                assert_eq!(travelled, distance, "ease {} over {} frames", ease, duration);
+            }
+        }
+    }
+}
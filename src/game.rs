@@ -5,9 +5,12 @@ use bevy_ggrs::AddRollbackCommandExtension;
 use bitflags::bitflags;
 use serde::{Deserialize, Serialize};
 
-use crate::{actions::{Action, MovementEffect}, fighters::{get_fighter, Fighter, FighterList}, AnimationData, SpriteRes};
+use crate::{actions::{Action, AttackKind, MovementEffect}, combat::{Hitboxes, Hurtboxes}, fighters::{get_fighter, Charge, Fighter, FighterList, Move}, stage::Stage, input_config::InputConfig, fixed::{Fixed, FixedVec2}, projectile::Projectile, throw::Throw, AnimationData, AnimationMode, SpriteRes};
 
-#[derive(Debug, Copy, Clone, Pod, Zeroable, PartialEq, Eq, Default, Deserialize, Serialize)]
+//-9.8/15 per frame, written out in 16.16 so the simulation never converts from floats
+const GRAVITY: Fixed = Fixed(-42817);
+
+#[derive(Debug, Copy, Clone, Pod, Zeroable, PartialEq, Eq, Hash, Default, Deserialize, Serialize, Reflect)]
 #[repr(C)]
 pub struct Inputs(u16);
 
@@ -15,47 +18,43 @@ impl Inputs {
     pub fn movement_input(&self) -> Inputs {
         *self & (Inputs::LEFT | Inputs::RIGHT | Inputs::DOWN | Inputs::UP)
     }
+    pub fn button_input(&self) -> Inputs {
+        *self & (Inputs::L | Inputs::M | Inputs::H | Inputs::S)
+    }
+    pub fn mirrored(&self) -> Inputs {
+        let mut mirrored = *self & !(Inputs::LEFT | Inputs::RIGHT);
+        if self.contains(Inputs::LEFT) {
+            mirrored |= Inputs::RIGHT;
+        }
+        if self.contains(Inputs::RIGHT) {
+            mirrored |= Inputs::LEFT;
+        }
+        mirrored
+    }
+    //turns LEFT/RIGHT into back/forward, so numpad 4 is always away from the opponent
+    pub fn relative_to(&self, facing: &FacingDirection) -> Inputs {
+        match facing {
+            FacingDirection::Right => *self,
+            FacingDirection::Left => self.mirrored()
+        }
+    }
     pub fn has(&self, other: &Inputs) -> bool {
         *self & *other == *other
     }
+    pub fn has_any(&self, other: &Inputs) -> bool {
+        *self & *other != Inputs::NONE
+    }
     pub fn has_dir(&self, other: &u8) -> bool {
-        self.movement_input() == match other {
-            1 => {
-                Inputs::LEFT | Inputs::DOWN
-            }
-            2 => {
-                Inputs::DOWN
-            }
-            3 => {
-                Inputs::RIGHT | Inputs::DOWN
-            }
-            4 => {
-                Inputs::LEFT
-            }
-            5 => {
-                Inputs::NONE
-            }
-            6 => {
-                Inputs::RIGHT
-            }
-            7 => {
-                Inputs::LEFT | Inputs::UP
-            }
-            8 => {
-                Inputs::UP
-            }
-            9 => {
-                Inputs::RIGHT | Inputs::UP
-            }
-            _ => {
-                warn!("TRIED TO MATCH NON-NUMPAD DIR INPUT: {}", other);
-                Inputs::NONE
-            }
-        }
+        self.movement_input() == Inputs::from_numpad(other)
     }
 
     pub fn has_dir_loose(&self, other: &u8) -> bool {
-        self.movement_input() & match other {
+        self.movement_input() & Inputs::from_numpad(other) != Inputs::NONE
+    }
+
+    //directions that make up a numpad input, held together for diagonals
+    pub fn from_numpad(other: &u8) -> Inputs {
+        match other {
             1 => {
                 Inputs::LEFT | Inputs::DOWN
             }
@@ -87,7 +86,7 @@ impl Inputs {
                 warn!("TRIED TO MATCH NON-NUMPAD DIR INPUT: {}", other);
                 Inputs::NONE
             }
-        } != Inputs::NONE
+        }
     }
 }
 
@@ -106,73 +105,337 @@ bitflags! {
     }
 }
 
-#[derive(Component)]
+#[derive(Resource, Reflect, Default, Hash, Clone, Copy)]
+#[reflect(Resource, Hash)]
+pub struct FrameCount {
+    pub frame: u32
+}
+
+pub fn increase_frame_count(mut frame_count: ResMut<FrameCount>) {
+    frame_count.frame += 1;
+}
+
+pub fn reset_frame_count(mut frame_count: ResMut<FrameCount>) {
+    frame_count.frame = 0;
+}
+
+#[derive(Component, Reflect, Default)]
+#[reflect(Component)]
 pub struct Player {
-    handle: usize,
+    pub handle: usize,
     pub fighter: Fighter
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32
+}
+
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
 pub struct ActionComponent {
-    pub actions: Vec<Action>
+    pub actions: Vec<Action>,
+    //what the current move's hitboxes have connected with, for on hit/block cancels
+    pub contact: Contact,
+    //handle of the player the current move last connected with
+    pub victim: Option<usize>
+}
+
+impl ActionComponent {
+    pub fn start_move(&mut self, fighter_move: &Move) {
+        self.actions = fighter_move.actions.to_owned();
+        self.contact = Contact::Whiff;
+        self.victim = None;
+    }
+    pub fn invulnerable_to(&self, kind: AttackKind) -> bool {
+        return self.actions.last().is_some_and(|action| action.invulnerable_to(kind));
+    }
+    pub fn can_cancel_into(&self, fighter_move: &Move) -> bool {
+        if let Some(action) = self.actions.last() {
+            return action.cancels.iter().any(|window| window.allows(action.frame, self.contact, fighter_move));
+        }
+        return false;
+    }
+}
+
+#[derive(Reflect, Default, Debug, Clone, Copy, PartialEq, Hash)]
+pub enum Contact {
+    #[default]
+    Whiff,
+    Hit,
+    Block
 }
 
-#[derive(Component, Default)]
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
 pub struct Movable {
     pub input: GameInput,
     pub movements: Vec<MovementData>,
     pub grounded: bool,
     pub facing: FacingDirection,
-    pub yspeed: f32,
-    pub gravity: f32,
+    pub position: FixedVec2,
+    pub yspeed: Fixed,
+    pub gravity: Fixed,
 }
 
-#[derive(Default)]
+#[derive(Component, Reflect, Default, Debug, Clone, Copy, PartialEq, Hash)]
+#[reflect(Component)]
+pub enum FighterState {
+    #[default]
+    Standing,
+    Crouching,
+    Airborne,
+    Hitstun(i32),
+    Blockstun(i32),
+    Knockdown(i32),
+    LandingRecovery(i32),
+    //frames left to tech the throw
+    Thrown(i32),
+}
+
+impl FighterState {
+    pub fn neutral(grounded: bool) -> FighterState {
+        if grounded {
+            FighterState::Standing
+        } else {
+            FighterState::Airborne
+        }
+    }
+    pub fn actionable(&self) -> bool {
+        match self {
+            FighterState::Standing | FighterState::Crouching | FighterState::Airborne => true,
+            _ => false
+        }
+    }
+    pub fn in_stun(&self) -> bool {
+        match self {
+            FighterState::Hitstun(_) | FighterState::Blockstun(_) => true,
+            _ => false
+        }
+    }
+    pub fn animation(&self) -> &str {
+        match self {
+            FighterState::Standing => "idle",
+            FighterState::Crouching => "crouch",
+            FighterState::Airborne => "jump",
+            FighterState::Hitstun(_) => "hitstun",
+            FighterState::Blockstun(_) => "block",
+            FighterState::Knockdown(_) => "knockdown",
+            FighterState::LandingRecovery(_) => "landing",
+            FighterState::Thrown(_) => "thrown",
+        }
+    }
+    pub fn animation_mode(&self) -> AnimationMode {
+        match self {
+            FighterState::Standing | FighterState::Crouching => AnimationMode::Loop,
+            _ => AnimationMode::Hold
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Hash, Reflect)]
 pub enum FacingDirection {
     #[default]
     Right,
     Left
 }
 
-#[derive(Default, Clone, Copy)]
+impl FacingDirection {
+    pub fn sign(&self) -> Fixed {
+        match self {
+            FacingDirection::Right => Fixed::ONE,
+            FacingDirection::Left => -Fixed::ONE
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Reflect)]
+pub struct Pushbox {
+    pub offset: FixedVec2,
+    pub size: FixedVec2
+}
+
+impl Default for Pushbox {
+    fn default() -> Self {
+        Pushbox { offset: FixedVec2::ZERO, size: FixedVec2::from_f32(24.0, 60.0) }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, Hash, Reflect)]
 pub struct MovementData {
-    distance: f32,
+    distance: Fixed,
     duration: i32,
-    ease: f32,
+    ease: Fixed,
     frame: i32,
-    direction: Vec2
+    direction: FixedVec2
 }
 
 impl MovementData {
-    pub fn new(distance: f32, duration: i32, ease: f32, frame: i32, direction: Vec2) -> MovementData {
+    pub fn new(distance: Fixed, duration: i32, ease: Fixed, frame: i32, direction: FixedVec2) -> MovementData {
         return MovementData { distance, duration, ease, frame: frame, direction }
     }
-    pub fn from_movement_effect(movement_effect: MovementEffect) -> MovementData {
-        return MovementData::new(movement_effect.distance, movement_effect.duration, movement_effect.ease, 0, movement_effect.direction);
+    pub fn from_movement_effect(movement_effect: MovementEffect, facing: &FacingDirection) -> MovementData {
+        let direction = FixedVec2::new(movement_effect.direction.x * facing.sign(), movement_effect.direction.y);
+        return MovementData::new(movement_effect.distance, movement_effect.duration, movement_effect.ease, 0, direction);
+    }
+    //distance covered after this many frames, the steps are differences of it so they add up to exactly distance
+    fn travelled(&self, frame: i32) -> Fixed {
+        let progress = Fixed::ONE - Fixed::from_int(frame) / Fixed::from_int(self.duration.max(1));
+        if self.ease > Fixed::ZERO {
+            return self.distance - self.distance * progress.powf(self.ease);
+        } else {
+            return self.distance * progress.powf(-self.ease) - self.distance;
+        }
+    }
+    //how far to move this frame, along direction
+    pub fn step(&mut self) -> Fixed {
+        let step = self.travelled(self.frame + 1) - self.travelled(self.frame);
+        self.frame += 1;
+        return step;
+    }
+    pub fn finished(&self) -> bool {
+        return self.frame >= self.duration;
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Debug, Clone, Hash, Reflect)]
 pub struct GameInput {
     input_log: VecDeque<Inputs>,
     smash_log: VecDeque<Inputs>,
+    //buttons let go of on each frame, lined up with smash_log
+    release_log: VecDeque<Inputs>,
+    //one per numpad direction, kept outside the logs since charges can be longer than them
+    charge: [ChargeState; 9],
+    //the buffer isn't cleared while the move that just started can still pick up the rest of a bigger version's buttons
+    upgrade: Option<ButtonUpgrade>,
+}
+
+#[derive(Default, Debug, Clone, Copy, Hash, Reflect)]
+pub struct ButtonUpgrade {
+    //index into the fighter's moves
+    pub from: usize,
+    pub frames_left: i32
+}
+
+//diagonals charge their cardinal directions too, so holding 1 charges 1, 2 and 4
+#[derive(Default, Debug, Clone, Copy, Hash, Reflect)]
+pub struct ChargeState {
+    pub held: i32,
+    //what held was when the direction was let go
+    pub stored: i32,
+    pub since_release: i32
+}
+
+impl GameInput {
+    pub fn current(&self) -> Inputs {
+        return *self.input_log.iter().rev().find(|input| **input != Inputs::BUFFERCLEAR).unwrap_or(&Inputs::NONE);
+    }
+    pub fn previous(&self) -> Inputs {
+        return *self.input_log.iter().rev().filter(|input| **input != Inputs::BUFFERCLEAR).nth(1).unwrap_or(&Inputs::NONE);
+    }
+    //inputs that went down this frame
+    pub fn pressed(&self) -> Inputs {
+        return self.current() & !self.previous();
+    }
+    //inputs that went up this frame
+    pub fn released(&self) -> Inputs {
+        return self.previous() & !self.current();
+    }
+    //every button pressed (or released) in the window frames leading up to the buffered frame
+    fn buttons_in_window(log: &VecDeque<Inputs>, buffered: usize, window: usize) -> Inputs {
+        return log.iter().rev().skip(buffered).take(window.max(1))
+            .take_while(|input| **input != Inputs::BUFFERCLEAR)
+            .fold(Inputs::NONE, |buttons, input| buttons | input.button_input());
+    }
+    //buttons can be pressed a few frames apart and still count as together, as long as the last one went down on the buffered frame
+    pub fn buttons_match(&self, buffered: usize, smash: Inputs, fighter_move: &Move) -> bool {
+        if !smash.has(&fighter_move.input.movement_input()) {
+            return false;
+        }
+        let buttons = fighter_move.input.button_input();
+        if buttons == Inputs::NONE {
+            return true;
+        }
+        let window = fighter_move.button_window as usize;
+        if smash.has_any(&buttons) && GameInput::buttons_in_window(&self.smash_log, buffered, window).has(&buttons) {
+            return true;
+        }
+        if !fighter_move.negative_edge {
+            return false;
+        }
+        let released = self.release_log.iter().rev().nth(buffered).copied().unwrap_or(Inputs::NONE);
+        return released.has_any(&buttons) && GameInput::buttons_in_window(&self.release_log, buffered, window).has(&buttons);
+    }
+    //takes the input relative to facing, once per frame
+    pub fn update_charge(&mut self, input: Inputs) {
+        for (i, charge) in self.charge.iter_mut().enumerate() {
+            let direction = Inputs::from_numpad(&(i as u8 + 1));
+            if direction != Inputs::NONE && input.movement_input().contains(direction) {
+                if charge.held == 0 {
+                    charge.stored = 0;
+                }
+                charge.held += 1;
+            } else if charge.held > 0 {
+                charge.stored = charge.held;
+                charge.held = 0;
+                charge.since_release = 0;
+            } else {
+                charge.since_release = charge.since_release.saturating_add(1);
+            }
+        }
+    }
+    pub fn charged(&self, charge: &Charge) -> bool {
+        let Some(state) = self.charge.get((charge.direction as usize).wrapping_sub(1)) else {
+            return false;
+        };
+        return state.held >= charge.frames || (state.since_release <= charge.retention && state.stored >= charge.frames);
+    }
+    //a released charge only gets used once
+    pub fn consume_charge(&mut self, charge: &Charge) {
+        if let Some(state) = self.charge.get_mut((charge.direction as usize).wrapping_sub(1)) {
+            state.stored = 0;
+        }
+    }
+    //stops the inputs so far from starting another move
+    pub fn clear_buffer(&mut self) {
+        self.smash_log.push_back(Inputs::BUFFERCLEAR);
+        self.input_log.push_back(Inputs::BUFFERCLEAR);
+        self.release_log.push_back(Inputs::BUFFERCLEAR);
+    }
+}
+
+pub fn starting_position(handle: usize) -> FixedVec2 {
+    if handle == 0 {
+        FixedVec2::new(Fixed::from_int(-50), Fixed::ZERO)
+    } else {
+        FixedVec2::new(Fixed::from_int(50), Fixed::ZERO)
+    }
 }
 
 pub fn spawn_players(mut commands: Commands, sprites: Res<SpriteRes>, fighter_list: Res<FighterList>){
-    spawn_player(&mut commands, &sprites, &fighter_list, 0, Vec3::new(-50., 0., 0.), "Ky".to_owned(), "Idle".to_owned());
-    spawn_player(&mut commands, &sprites, &fighter_list, 1, Vec3::new(50., 0., 0.), "Id".to_owned(), "Idle".to_owned());
+    spawn_player(&mut commands, &sprites, &fighter_list, 0, starting_position(0), "Ky".to_owned(), "Idle".to_owned());
+    spawn_player(&mut commands, &sprites, &fighter_list, 1, starting_position(1), "Id".to_owned(), "Idle".to_owned());
 }
 
-pub fn spawn_player(commands: &mut Commands, sprites: &Res<SpriteRes>, fighter_list: &Res<FighterList>, handle: usize, position: Vec3, character: String, starting_animation: String){
+pub fn spawn_player(commands: &mut Commands, sprites: &Res<SpriteRes>, fighter_list: &Res<FighterList>, handle: usize, position: FixedVec2, character: String, starting_animation: String){
     //TODO: make a default invisible "loading" sprite instead of grabbing the atlas manually
     if let Some(atlas) = sprites.atlases.get(&character.to_lowercase()) {
+        let fighter = get_fighter(character.to_owned(), fighter_list);
         commands.spawn((
-            Player{ handle: handle, fighter: get_fighter(character.to_owned(), fighter_list) },
-            Movable { ..default() },
+            Hurtboxes { boxes: fighter.hurtboxes.stand.to_owned(), ..default() },
+            Health { current: fighter.health, max: fighter.health },
+            FighterState::Standing,
+            Player{ handle: handle, fighter },
+            Movable { position, ..default() },
             ActionComponent { ..default() },
+            Hitboxes { ..default() },
+            Throw { ..default() },
+            Combo { ..default() },
             AnimationData::new(starting_animation, atlas),
             SpriteSheetBundle {
-                transform: Transform::from_translation(position),
+                transform: Transform::from_translation(position.to_vec2().extend(0.0)),
                 texture_atlas: atlas.atlas.to_owned(),
                 sprite: TextureAtlasSprite::new(0),
                 ..default()
@@ -181,6 +444,22 @@ pub fn spawn_player(commands: &mut Commands, sprites: &Res<SpriteRes>, fighter_l
     }
 }
 
+//spawn_player without the sprite and rollback, standing on the floor
+#[cfg(test)]
+pub fn spawn_test_fighter(world: &mut World, handle: usize, fighter: Fighter) -> Entity {
+    return world.spawn((
+        Hurtboxes { boxes: fighter.hurtboxes.stand.to_owned(), ..default() },
+        Health { current: fighter.health, max: fighter.health },
+        FighterState::Standing,
+        Player { handle, fighter },
+        Movable { position: starting_position(handle), grounded: true, ..default() },
+        ActionComponent::default(),
+        Hitboxes::default(),
+        Throw::default(),
+        Combo::default()
+    )).id();
+}
+
 pub fn input(
     keyboard_input: Res<Input<KeyCode>>,
     gamepads: Res<Gamepads>,
@@ -273,48 +552,84 @@ pub fn input(
     inp
 }
 
-pub fn set_player_input(inputs: Vec<Inputs>, mut players: Query<(&mut Movable, &Player, &mut ActionComponent)>) {
-	let log_length = 30; //how long the motion buffer should last
-	let buffer_length = 1; //how long buffered moves should buffer for (for getups and cancels and such)
+pub fn set_player_input(inputs: Vec<Inputs>, mut players: Query<(&mut Movable, &Player, &mut ActionComponent, &FighterState)>, config: &InputConfig) {
+	let log_length = config.log_length.max(1);
+	let buffer_length = config.buffer_length.max(1);
     
-    for (mut movable, player, mut actions) in &mut players {
-
-        let mut smash_input = inputs[player.handle];
-        if let Some(last_input) = movable.input.input_log.back(){
-            smash_input &= !*last_input;
+    for (mut movable, player, mut actions, state) in &mut players {
+        //cleared before this frame's input goes in, so a fresh press this frame is still read
+        if let Some(mut upgrade) = movable.input.upgrade {
+            upgrade.frames_left -= 1;
+            if upgrade.frames_left <= 0 || actions.actions.len() == 0 || !state.actionable() {
+                movable.input.upgrade = None;
+                movable.input.clear_buffer();
+            } else {
+                movable.input.upgrade = Some(upgrade);
+            }
         }
+        let upgrade = movable.input.upgrade;
+
+        //against the last real input, a BUFFERCLEAR would hide every press on the frame after it
+        let smash_input = inputs[player.handle] & !movable.input.current();
         movable.input.smash_log.push_back(smash_input);
         if movable.input.smash_log.len() > log_length {
             movable.input.smash_log.pop_front();
         }
+        let release_input = movable.input.current().button_input() & !inputs[player.handle];
+        movable.input.release_log.push_back(release_input);
+        if movable.input.release_log.len() > log_length {
+            movable.input.release_log.pop_front();
+        }
         movable.input.input_log.push_back(inputs[player.handle]);
         if movable.input.input_log.len() > log_length {
             movable.input.input_log.pop_front();
         }
         
-        for potentialmove in &player.fighter.moves {
-            for buffered_input in movable.input.smash_log.to_owned().iter().rev().take(buffer_length) {
+        //motions are written facing right, so read the logs relative to where the fighter is facing
+        let facing = movable.facing;
+        movable.input.update_charge(inputs[player.handle].relative_to(&facing));
+        let mut candidates: Vec<(usize, &Move)> = vec![];
+        for (index, potentialmove) in player.fighter.moves.iter().enumerate() {
+            //only a bigger version of the move that just started can take over from it
+            if upgrade.is_some_and(|upgrade| !player.fighter.moves[upgrade.from].upgrades_to(potentialmove)) {
+                continue;
+            }
+            if !potentialmove.usable(movable.grounded) {
+                continue;
+            }
+            if potentialmove.charge.is_some_and(|charge| !movable.input.charged(&charge)) {
+                continue;
+            }
+            for (i, buffered_input) in movable.input.smash_log.to_owned().iter().rev().take(buffer_length).enumerate() {
                 if *buffered_input == Inputs::BUFFERCLEAR {
                     break;
                 }
-                if buffered_input.has(&potentialmove.input) {
+                if movable.input.buttons_match(i, buffered_input.relative_to(&facing), potentialmove) {
                     let mut input_iter = potentialmove.motion.iter().rev().peekable();
                     let mut previous_motion_input = 5;
                     let mut previous_input = Inputs::NONE;
-
+                    let motion_window = potentialmove.motion_window.unwrap_or(config.motion_window);
+                    let motion_gap = potentialmove.motion_gap.unwrap_or(config.motion_gap);
+                    let mut last_matched: Option<usize> = None;
 
                     //make it so moves without a button to press take the last button press as the input
                     if !(potentialmove.input == Inputs::NONE && 
                         movable.input.input_log.back().is_some() && 
                         *movable.input.input_log.back().unwrap() == Inputs::NONE) {
-                        for input in movable.input.input_log.iter().rev() {
-                            if *input == Inputs::BUFFERCLEAR {
+                        for (frame, input) in movable.input.input_log.iter().rev().enumerate() {
+                            if *input == Inputs::BUFFERCLEAR || frame > motion_window {
+                                break;
+                            }
+                            //too long since the last direction of the motion, nothing further back can count
+                            if last_matched.is_some_and(|last_frame| frame - last_frame > motion_gap) {
                                 break;
                             }
+                            let input = &input.relative_to(&facing);
                             if let Some(iter_input) = input_iter.next_if(
                                 |&x| input.has_dir(x) || (*x != 5 && (input.movement_input() & previous_input).has_dir(x))
                             ) {
                                 previous_motion_input = *iter_input;
+                                last_matched = Some(frame);
                             } else {
                                 if !(input.has_dir(&5) || 
                                 (input_iter.peek().is_some() && input.has_dir_loose(input_iter.peek().unwrap())) || 
@@ -326,56 +641,419 @@ pub fn set_player_input(inputs: Vec<Inputs>, mut players: Query<(&mut Movable, &
                         }
                     }
                     
-                    if input_iter.peek().is_none() { 
-                        if actions.actions.len() == 0 {
-                            println!("Move {} started!", potentialmove.name);
-                            actions.actions = potentialmove.actions.to_owned();
-
-                            if potentialmove.input != Inputs::NONE {
-                                movable.input.smash_log.push_back(Inputs::BUFFERCLEAR);
-                                movable.input.input_log.push_back(Inputs::BUFFERCLEAR);
-                            }
+                    if input_iter.peek().is_none() {
+                        if upgrade.is_some() || (actions.actions.len() == 0 && state.actionable() && potentialmove.from_idle) || actions.can_cancel_into(potentialmove) {
+                            candidates.push((index, potentialmove));
+                            break;
                         }
                     }
                 }
             }
         }
+
+        //ties keep file order, so the first move in the RON still wins if nothing else separates them
+        let Some((chosen_index, chosen)) = candidates.iter().rev().max_by_key(|(_, candidate)| candidate.priority_key()).copied() else {
+            continue;
+        };
+        if candidates.len() > 1 {
+            let matched: Vec<String> = candidates.iter().map(|(_, candidate)| {
+                let (priority, _, motion, buttons, category) = candidate.priority_key();
+                format!("{} (priority {}, motion {}, buttons {}, category {})", candidate.name, priority, motion, buttons, category)
+            }).collect();
+            debug!("Moves matched: {}, picked {}", matched.join(", "), chosen.name);
+        }
+        println!("Move {} started!", chosen.name);
+        actions.start_move(chosen);
+        if let Some(charge) = &chosen.charge {
+            movable.input.consume_charge(charge);
+        }
+
+        if chosen.input != Inputs::NONE {
+            if chosen.button_window > 1 && player.fighter.moves.iter().any(|other| chosen.upgrades_to(other)) {
+                movable.input.upgrade = Some(ButtonUpgrade { from: chosen_index, frames_left: chosen.button_window });
+            } else {
+                movable.input.upgrade = None;
+                movable.input.clear_buffer();
+            }
+        }
     }
 }
 
-pub fn movable_system(mut movables: Query<(&mut Transform, &mut Movable)>) {
-    for (mut transform, mut movable) in &mut movables {
-        let mut move_delta = Vec2::ZERO;
-        movable.movements.retain_mut(|movement_data| {
-            let &mut MovementData { distance, duration, ease, frame, direction } = movement_data;
-            if ease > 0.0 {
-                move_delta += distance * ((1.0 - (frame as f32) / (duration as f32)).powf(ease) - (1.0 - (frame as f32 + 1.0) / (duration as f32)).powf(ease)) * direction;
+pub fn fighter_state_system(mut players: Query<(&Player, &Movable, &ActionComponent, &mut FighterState)>) {
+    for (player, movable, actions, mut state) in &mut players {
+        let next_state = match *state {
+            FighterState::Standing | FighterState::Crouching => {
+                if !movable.grounded {
+                    FighterState::Airborne
+                } else if movable.input.current().has(&Inputs::DOWN) && actions.actions.len() == 0 {
+                    FighterState::Crouching
+                } else {
+                    FighterState::Standing
+                }
+            }
+            FighterState::Airborne => {
+                //jumps can still have actions left when they land, they don't skip the recovery
+                if !movable.grounded {
+                    FighterState::Airborne
+                } else if player.fighter.landing_recovery > 0 {
+                    FighterState::LandingRecovery(player.fighter.landing_recovery)
+                } else {
+                    FighterState::Standing
+                }
+            }
+            FighterState::Hitstun(frames) => {
+                if frames > 1 { FighterState::Hitstun(frames - 1) } else { FighterState::neutral(movable.grounded) }
+            }
+            FighterState::Blockstun(frames) => {
+                if frames > 1 { FighterState::Blockstun(frames - 1) } else { FighterState::neutral(movable.grounded) }
+            }
+            FighterState::Knockdown(frames) => {
+                //the knockdown timer only starts once the fighter has hit the floor
+                if !movable.grounded {
+                    FighterState::Knockdown(frames)
+                } else if frames > 1 {
+                    FighterState::Knockdown(frames - 1)
+                } else {
+                    FighterState::Standing
+                }
+            }
+            FighterState::LandingRecovery(frames) => {
+                if frames > 1 { FighterState::LandingRecovery(frames - 1) } else { FighterState::Standing }
+            }
+            //throw_system lets go of thrown fighters
+            FighterState::Thrown(frames) => FighterState::Thrown((frames - 1).max(0))
+        };
+        if *state != next_state {
+            *state = next_state;
+        }
+    }
+}
+
+pub fn update_facing(mut players: Query<(Entity, &mut Movable, &ActionComponent, &FighterState), With<Player>>) {
+    let positions: Vec<(Entity, Fixed)> = players.iter().map(|(entity, movable, _, _)| (entity, movable.position.x)).collect();
+    for (entity, mut movable, actions, state) in &mut players {
+        //only turn around while grounded and free to act, so moves and jumps keep their direction
+        if !movable.grounded || actions.actions.len() > 0 {
+            continue;
+        }
+        match state {
+            FighterState::Standing | FighterState::Crouching => {}
+            _ => continue
+        }
+        if let Some((_, opponent_x)) = positions.iter().find(|(other, _)| *other != entity) {
+            let facing = if *opponent_x < movable.position.x {
+                FacingDirection::Left
+            } else if *opponent_x > movable.position.x {
+                FacingDirection::Right
             } else {
-                move_delta += distance * ((1.0 - (frame as f32 + 1.0) / (duration as f32)).powf(-ease) - (1.0 - (frame as f32) / (duration as f32)).powf(-ease)) * direction;
+                movable.facing
+            };
+            if movable.facing != facing {
+                movable.facing = facing;
             }
-            movement_data.frame += 1;
-            return movement_data.frame < duration;
+        }
+    }
+}
+
+pub fn facing_sprite_system(mut movables: Query<(&Movable, &mut TextureAtlasSprite)>) {
+    for (movable, mut sprite) in &mut movables {
+        let flip = movable.facing == FacingDirection::Left;
+        if sprite.flip_x != flip {
+            sprite.flip_x = flip;
+        }
+    }
+}
+
+pub fn state_animation_system(
+    mut players: Query<(&Player, &FighterState, &ActionComponent, &mut AnimationData)>,
+    sprites: Res<SpriteRes>
+) {
+    for (player, state, actions, mut animation) in &mut players {
+        //thrown fighters are animated by whoever is throwing them
+        if actions.actions.len() > 0 || matches!(state, FighterState::Thrown(_)) {
+            continue;
+        }
+        if let Some(atlas) = sprites.atlases.get(&player.fighter.name.to_lowercase()) {
+            animation.set_animation(state.animation(), 1.0, state.animation_mode(), atlas);
+        }
+    }
+}
+
+pub fn movable_system(mut movables: Query<&mut Movable, Without<Projectile>>, stage: Res<Stage>) {
+    for mut movable in &mut movables {
+        let mut move_delta = FixedVec2::ZERO;
+        movable.movements.retain_mut(|movement_data| {
+            move_delta += movement_data.direction * movement_data.step();
+            return !movement_data.finished();
         });
-        movable.yspeed += movable.gravity;
+        let gravity = movable.gravity;
+        movable.yspeed += gravity;
         move_delta.y += movable.yspeed;
 
-        //add better collision here
-        transform.translation += move_delta.extend(0.0);
-        if transform.translation.y <= -50.0 {
-            transform.translation.y = -50.0;
+        movable.position += move_delta;
+        if movable.position.y <= stage.0.floor {
+            movable.position.y = stage.0.floor;
             movable.grounded = true;
         } else {
             movable.grounded = false;
         }
 
-        movable.gravity = -9.8/15.;
+        movable.gravity = GRAVITY;
+    }
+}
+
+//the simulation only works on Movable.position, this copies it over to the Transform for rendering
+pub fn sync_transforms(mut movables: Query<(&Movable, &mut Transform)>) {
+    for (movable, mut transform) in &mut movables {
+        let translation = movable.position.to_vec2().extend(transform.translation.z);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
+
+pub fn pushbox_center(movable: &Movable, pushbox: &Pushbox) -> FixedVec2 {
+    movable.position + FixedVec2::new(pushbox.offset.x * movable.facing.sign(), pushbox.offset.y)
+}
+
+//pushes overlapping fighters apart, every push is worked out before any of them are applied so query order doesn't matter
+pub fn soft_collision(mut fighters: Query<(&Player, &mut Movable)>) {
+    let mut bodies: Vec<(usize, FixedVec2, FixedVec2)> = fighters.iter()
+        .map(|(player, movable)| (player.handle, pushbox_center(movable, &player.fighter.pushbox), player.fighter.pushbox.size))
+        .collect();
+    bodies.sort_by_key(|(handle, _, _)| *handle);
+
+    let mut pushes = vec![Fixed::ZERO; bodies.len()];
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let (_, a_center, a_size) = bodies[i];
+            let (_, b_center, b_size) = bodies[j];
+            let overlap_x = (a_size.x + b_size.x).half() - (a_center.x - b_center.x).abs();
+            let overlap_y = (a_size.y + b_size.y).half() - (a_center.y - b_center.y).abs();
+            if overlap_x <= Fixed::ZERO || overlap_y <= Fixed::ZERO {
+                continue;
+            }
+            //fighters on the exact same spot get separated by player handle
+            let push = if a_center.x <= b_center.x { -overlap_x.half() } else { overlap_x.half() };
+            pushes[i] += push;
+            pushes[j] -= push;
+        }
+    }
+
+    for (player, mut movable) in &mut fighters {
+        if let Some(index) = bodies.iter().position(|(handle, _, _)| *handle == player.handle) {
+            if pushes[index] != Fixed::ZERO {
+                movable.position.x += pushes[index];
+            }
+        }
     }
 }
 
-pub fn soft_collision() {
+//keeps fighters inside the stage walls, and if a fighter is pinned in the corner the other one takes the whole push
+pub fn hard_collision(mut fighters: Query<(&Player, &mut Movable)>, stage: Res<Stage>) {
+    let stage = &stage.0;
+    for (player, mut movable) in &mut fighters {
+        let pushbox = &player.fighter.pushbox;
+        let center = pushbox_center(&movable, pushbox);
+        let left = stage.left_wall + pushbox.size.x.half();
+        let right = stage.right_wall - pushbox.size.x.half();
+        if center.x < left {
+            movable.position.x += left - center.x;
+        } else if center.x > right {
+            movable.position.x -= center.x - right;
+        }
+    }
+
+    let mut bodies: Vec<(usize, FixedVec2, FixedVec2, bool)> = fighters.iter()
+        .map(|(player, movable)| {
+            let pushbox = &player.fighter.pushbox;
+            let center = pushbox_center(movable, pushbox);
+            let cornered = center.x - pushbox.size.x.half() <= stage.left_wall || center.x + pushbox.size.x.half() >= stage.right_wall;
+            (player.handle, center, pushbox.size, cornered)
+        })
+        .collect();
+    bodies.sort_by_key(|(handle, _, _, _)| *handle);
 
+    let mut pushes = vec![Fixed::ZERO; bodies.len()];
+    for i in 0..bodies.len() {
+        for j in (i + 1)..bodies.len() {
+            let (_, a_center, a_size, a_cornered) = bodies[i];
+            let (_, b_center, b_size, b_cornered) = bodies[j];
+            let overlap_x = (a_size.x + b_size.x).half() - (a_center.x - b_center.x).abs();
+            let overlap_y = (a_size.y + b_size.y).half() - (a_center.y - b_center.y).abs();
+            if overlap_x <= Fixed::ZERO || overlap_y <= Fixed::ZERO || a_cornered == b_cornered {
+                continue;
+            }
+            let push = if a_center.x < b_center.x { -overlap_x } else { overlap_x };
+            if a_cornered {
+                pushes[j] -= push;
+            } else {
+                pushes[i] += push;
+            }
+        }
+    }
+
+    for (player, mut movable) in &mut fighters {
+        if let Some(index) = bodies.iter().position(|(handle, _, _, _)| *handle == player.handle) {
+            if pushes[index] != Fixed::ZERO {
+                movable.position.x += pushes[index];
+            }
+        }
+    }
 }
 
-pub fn hard_collision() {
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fighters::get_default_fighter, Effect, MoveCategory};
+
+    #[derive(Resource, Default)]
+    struct TestInput(Inputs);
+
+    fn test_input_system(input: Res<TestInput>, players: Query<(&mut Movable, &Player, &mut ActionComponent, &FighterState)>) {
+        set_player_input(vec![input.0], players, &InputConfig::default());
+    }
+
+    //names every action's sprite after its move, so the started one can be told apart
+    fn tagged(mut fighter: Fighter) -> Fighter {
+        for fighter_move in &mut fighter.moves {
+            for action in &mut fighter_move.actions {
+                action.sprite = fighter_move.name.to_owned();
+            }
+        }
+        return fighter;
+    }
+
+    //the two DPs from the default fighter
+    fn dp_fighter() -> Fighter {
+        let mut fighter = get_default_fighter("test".to_owned());
+        fighter.moves.retain(|fighter_move| fighter_move.name == "DP" || fighter_move.name == "Spec DP");
+        for fighter_move in &mut fighter.moves {
+            fighter_move.actions = vec![Action { effects: vec![Effect::Wait(20)], ..default() }];
+        }
+        return tagged(fighter);
+    }
+
+    fn ky() -> Fighter {
+        return tagged(ron::de::from_str(include_str!("../assets/Ky/ky.fighter.ron")).unwrap());
+    }
+
+    fn run_inputs(fighter: Fighter, inputs: Vec<Inputs>) -> (App, Entity) {
+        let mut app = App::new();
+        app.init_resource::<TestInput>();
+        app.add_systems(Update, test_input_system);
+        let player = spawn_test_fighter(&mut app.world, 0, fighter);
+        for input in inputs {
+            app.world.resource_mut::<TestInput>().0 = input;
+            app.update();
+        }
+        return (app, player);
+    }
+
+    fn started_move(fighter: Fighter, inputs: Vec<Inputs>) -> Option<String> {
+        let (app, player) = run_inputs(fighter, inputs);
+        return app.world.get::<ActionComponent>(player).unwrap().actions.last().map(|action| action.sprite.to_owned());
+    }
+
+    fn dp_motion() -> Vec<Inputs> {
+        return vec![Inputs::RIGHT, Inputs::DOWN, Inputs::DOWN | Inputs::RIGHT];
+    }
+
+    #[test]
+    fn slash_then_heavy_starts_spec_dp() {
+        let mut inputs = dp_motion();
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::S);
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::S | Inputs::H);
+        assert_eq!(started_move(dp_fighter(), inputs), Some("Spec DP".to_owned()));
+    }
+
+    #[test]
+    fn heavy_then_slash_upgrades_to_spec_dp() {
+        let mut inputs = dp_motion();
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::H);
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::H | Inputs::S);
+        assert_eq!(started_move(dp_fighter(), inputs), Some("Spec DP".to_owned()));
+    }
+
+    #[test]
+    fn heavy_alone_stays_dp() {
+        let mut inputs = dp_motion();
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::H);
+        inputs.extend([Inputs::NONE, Inputs::NONE, Inputs::S]);
+        assert_eq!(started_move(dp_fighter(), inputs), Some("DP".to_owned()));
+    }
+
+    #[test]
+    fn forward_and_light_starts_stand_l() {
+        assert_eq!(started_move(ky(), vec![Inputs::RIGHT | Inputs::L]), Some("StandL".to_owned()));
+    }
+
+    #[test]
+    fn press_on_upgrade_expiry_is_read() {
+        let mut inputs = dp_motion();
+        inputs.push(Inputs::DOWN | Inputs::RIGHT | Inputs::H);
+        //the window closes and the buffer is cleared on the third frame after DP started
+        inputs.extend([Inputs::NONE, Inputs::NONE, Inputs::L]);
+        let (app, player) = run_inputs(dp_fighter(), inputs);
+        let input = &app.world.get::<Movable>(player).unwrap().input;
+        assert_eq!(input.smash_log.back(), Some(&Inputs::L));
+        assert!(input.upgrade.is_none());
+    }
+
+    fn hold(input: &mut GameInput, direction: Inputs, frames: i32) {
+        for _ in 0..frames {
+            input.update_charge(direction);
+        }
+    }
+
+    //[4]6+L with 30 frames of charge
+    fn charge_fighter() -> Fighter {
+        let mut fighter = get_default_fighter("test".to_owned());
+        fighter.moves = vec![Move {
+            name: "Sonic".to_owned(),
+            category: MoveCategory::Special,
+            charge: Some(Charge { direction: 4, frames: 30, retention: 8 }),
+            motion: vec![6],
+            input: Inputs::L,
+            actions: vec![Action { effects: vec![Effect::Wait(20)], ..default() }],
+            ..default()
+        }];
+        return tagged(fighter);
+    }
+
+    #[test]
+    fn charged_back_forward_starts() {
+        let mut inputs = vec![Inputs::LEFT; 40];
+        inputs.push(Inputs::RIGHT | Inputs::L);
+        assert_eq!(started_move(charge_fighter(), inputs), Some("Sonic".to_owned()));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn uncharged_back_forward_does_nothing() {
+        let mut inputs = vec![Inputs::LEFT; 10];
+        inputs.push(Inputs::RIGHT | Inputs::L);
+        assert_eq!(started_move(charge_fighter(), inputs), None);
+    }
+
+    #[test]
+    fn charge_runs_out_after_retention() {
+        let charge = Charge { direction: 4, frames: 30, retention: 8 };
+        let mut input = GameInput::default();
+        hold(&mut input, Inputs::LEFT, 30);
+        //the frame it's let go plus the retention
+        hold(&mut input, Inputs::NONE, 1 + charge.retention);
+        assert!(input.charged(&charge));
+        hold(&mut input, Inputs::NONE, 1);
+        assert!(!input.charged(&charge));
+    }
+
+    #[test]
+    fn diagonal_charges_its_cardinals() {
+        let mut input = GameInput::default();
+        hold(&mut input, Inputs::LEFT | Inputs::DOWN, 30);
+        for direction in [1, 2, 4] {
+            assert!(input.charged(&Charge { direction, frames: 30, retention: 8 }), "direction {}", direction);
+        }
+        assert!(!input.charged(&Charge { direction: 6, frames: 30, retention: 8 }));
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::{prelude::*, utils::BoxedFuture, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}};
+use serde::{Deserialize, Serialize};
+
+//input leniency for every fighter, loaded from game.input.ron, moves can override the motion windows
+#[derive(Debug, Deserialize, Serialize, TypeUuid, TypePath, Clone)]
+#[uuid = "a3d85e10-7c42-4b9f-8e16-52f0c9b7d2e4"]
+#[serde(default)]
+pub struct InputConfig {
+    //how many frames of input are kept for reading motions
+    pub log_length: usize,
+    //how long buffered moves should buffer for (for getups and cancels and such)
+    pub buffer_length: usize,
+    //max frames from the first direction of a motion to the button
+    pub motion_window: usize,
+    //max frames between two directions of a motion
+    pub motion_gap: usize
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig {
+            log_length: 30,
+            buffer_length: 1,
+            motion_window: 30,
+            motion_gap: 15
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct InputSettings(pub InputConfig);
+
+#[derive(Default)]
+pub struct InputConfigLoader;
+
+impl AssetLoader for InputConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let custom_asset = ron::de::from_bytes::<InputConfig>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["input.ron"]
+    }
+}
+
+pub fn input_config_setup(
+    asset_server: Res<AssetServer>,
+    input_configs: Res<Assets<InputConfig>>,
+    mut settings: ResMut<InputSettings>
+){
+    let _handles: Vec<HandleUntyped> = asset_server.load_folder("./").unwrap();
+    for _handle in _handles {
+        let handle = _handle.typed_weak();
+        if let Some(loaded_config) = input_configs.get(&handle) {
+            settings.0 = loaded_config.to_owned();
+            return;
+        }
+    }
+}
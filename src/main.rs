@@ -5,6 +5,14 @@ mod netcode;
 mod menu;
 mod fighters;
 mod actions;
+mod combat;
+mod round;
+mod stage;
+mod fixed;
+mod projectile;
+mod throw;
+mod rules;
+mod input_config;
 
 use crate::game::*;
 use crate::editor::*;
@@ -12,10 +20,19 @@ use crate::netcode::*;
 use crate::menu::*;
 use crate::fighters::*;
 use crate::actions::*;
+use crate::combat::*;
+use crate::round::*;
+use crate::stage::*;
+use crate::fixed::*;
+use crate::projectile::*;
+use crate::throw::*;
+use crate::rules::*;
+use crate::input_config::*;
 use backend::*;
 use bevy::prelude::*;
 use bevy::utils::hashbrown::HashMap;
 use bevy::asset::LoadState;
+use bevy::ecs::schedule::SystemConfigs;
 use bevy::render::camera::ScalingMode;
 use bevy_ggrs::{GgrsAppExtension, GgrsPlugin, GgrsSchedule};
 use bevy_egui::EguiPlugin;
@@ -35,6 +52,7 @@ pub enum GameState {
     Loading,
     Menu,
     Gameplay,
+    MatchEnd,
 }
 
 #[derive(Resource, Default)]
@@ -51,6 +69,10 @@ struct PlayerBundle {
 fn main() {
     let mut app = App::new();
 
+    if let Some(synctest_config) = SyncTestConfig::from_args() {
+        app.insert_resource(synctest_config);
+    }
+
     app
         .add_state::<AppState>()
         .add_state::<GameState>()
@@ -70,11 +92,21 @@ fn main() {
             GgrsPlugin::<GGRSConfig>::new()
             .with_update_frequency(FPS)
             .with_input_system(network_input)
-            .register_rollback_component::<Transform>()
-            //.register_rollback_component::<ActionComponent>()
-            //.register_rollback_component::<Movable>()
-            //.register_rollback_component::<Checksum>()
-            //.register_rollback_resource::<FrameCount>()
+            .register_rollback_component::<Player>()
+            .register_rollback_component::<ActionComponent>()
+            .register_rollback_component::<Movable>()
+            .register_rollback_component::<Health>()
+            .register_rollback_component::<FighterState>()
+            .register_rollback_component::<Hitboxes>()
+            .register_rollback_component::<Hurtboxes>()
+            .register_rollback_component::<AnimationData>()
+            .register_rollback_component::<Projectile>()
+            .register_rollback_component::<Throw>()
+            .register_rollback_component::<Combo>()
+            .register_rollback_resource::<FrameCount>()
+            .register_rollback_resource::<ProjectileIds>()
+            .register_rollback_resource::<Checksum>()
+            .register_rollback_resource::<RoundState>()
         )
 
         .add_asset::<AnimationInfo>()
@@ -83,17 +115,37 @@ fn main() {
         .add_asset::<Fighter>()
         .init_asset_loader::<FighterLoader>()
 
+        .add_asset::<StageInfo>()
+        .init_asset_loader::<StageLoader>()
+
+        .add_asset::<GameRules>()
+        .init_asset_loader::<GameRulesLoader>()
+
+        .add_asset::<InputConfig>()
+        .init_asset_loader::<InputConfigLoader>()
+
+        .add_event::<HitEvent>()
+
         .init_resource::<FileHandles>()
         .init_resource::<EditorUiState>()
+        .init_resource::<RoundState>()
+        .init_resource::<FrameCount>()
+        .init_resource::<ProjectileIds>()
+        .init_resource::<Checksum>()
+        .init_resource::<ChecksumHistory>()
+        .init_resource::<DesyncWarning>()
+        .init_resource::<Stage>()
+        .init_resource::<Rules>()
+        .init_resource::<InputSettings>()
         .insert_resource(SpriteRes { atlases: HashMap::new() })
         .insert_resource(FighterList (HashMap::new()))
 
         .add_systems(OnEnter(AppState::Setup), load_files)
         .add_systems(Update, check_files.run_if(in_state(AppState::Setup)))
-        .add_systems(OnEnter(AppState::Finished), (setup, spriteset_setup, fighters_setup))
+        .add_systems(OnEnter(AppState::Finished), (setup, spriteset_setup, fighters_setup, stage_setup, rules_setup, input_config_setup))
 
         //Backend Systems
-        .add_systems(Update, animation_system)
+        .add_systems(Update, (projectile_sprite_system, sync_transforms, facing_sprite_system, animation_sprite_system).chain())
 
         //Menus
         .add_systems(OnEnter(GameState::Menu), menu_setup)
@@ -103,31 +155,67 @@ fn main() {
         //Fighter Editor
         .add_systems(Update, (editor_system).run_if(in_state(NetworkState::Offline).and_then(in_state(GameState::Gameplay))))
 
+        //Match results
+        .add_systems(Update, (match_end_system).run_if(in_state(GameState::MatchEnd)))
+
+        //Combo counter
+        .add_systems(Update, (combo_display_system).run_if(in_state(GameState::Gameplay)))
+
         //Connecting to online
         .add_systems(OnEnter(NetworkState::Connecting), start_matchbox_socket)
+        .add_systems(OnEnter(NetworkState::SyncTest), start_synctest_session)
         .add_systems(Update, (wait_for_players).run_if(in_state(NetworkState::Connecting)))
+        .add_systems(Update, (desync_system, desync_warning_system).chain().run_if(in_state(NetworkState::Online)))
 
         //Gameplay, both offline and online
-        .add_systems(OnEnter(GameState::Gameplay), spawn_players)
-        .add_systems(FixedUpdate, movable_system)
+        .add_systems(OnEnter(GameState::Gameplay), (reset_round_state, reset_frame_count, spawn_players))
+        .add_systems(OnExit(GameState::Gameplay), (despawn_players, despawn_projectiles))
 
         //Offline gameplay
-        .add_systems(FixedUpdate, (offline_apply_inputs, parse_actions).run_if(in_state(NetworkState::Offline).and_then(in_state(GameState::Gameplay))))
+        .add_systems(
+            FixedUpdate,
+            (offline_apply_inputs, simulation_systems())
+                .chain().run_if(in_state(NetworkState::Offline).and_then(in_state(GameState::Gameplay))),
+        )
 
         //Online Gameplay (rollback schedule)
         .add_systems(
             GgrsSchedule,
-            (
-                apply_inputs,
-                parse_actions,
-                //increase_frame_count,
-                //checksum_players,
-            )
-                .chain().run_if(in_state(NetworkState::Online)),
+            (apply_inputs, simulation_systems())
+                .chain().run_if(in_state(NetworkState::Online).or_else(in_state(NetworkState::SyncTest))),
         )
         .run();
 }
 
+//everything after inputs, shared by the offline and rollback schedules so they can't drift apart
+fn simulation_systems() -> SystemConfigs {
+    return (
+        (
+            parse_actions,
+            movable_system,
+            projectile_movement,
+            throw_system,
+            soft_collision,
+            hard_collision,
+            update_hurtboxes,
+            hitbox_system,
+            grab_system,
+            apply_hits,
+        ).chain(),
+        (
+            projectile_system,
+            combo_system,
+            fighter_state_system,
+            update_facing,
+            state_animation_system,
+            animation_system,
+            round_system,
+            increase_frame_count,
+            checksum_players,
+        ).chain(),
+    ).chain();
+}
+
 fn load_files(mut file_handles: ResMut<FileHandles>, asset_server: Res<AssetServer>) {
     // load multiple, individual sprites from a folder
     file_handles.handles = asset_server.load_folder("./").unwrap();
@@ -147,10 +235,18 @@ fn check_files(
 }
 
 fn setup(mut commands: Commands,
-    mut next_state: ResMut<NextState<GameState>>,) {
+    mut next_state: ResMut<NextState<GameState>>,
+    mut network_state: ResMut<NextState<NetworkState>>,
+    synctest: Option<Res<SyncTestConfig>>,) {
     let mut camera_bundle = Camera2dBundle::default();
     camera_bundle.projection.scaling_mode = ScalingMode::Fixed { width: 432.0, height: 243.0 };
     commands.spawn(camera_bundle);
 
-    next_state.set(GameState::Menu);
+    if synctest.is_some() {
+        //skip the menu and matchmaking, both players are local
+        network_state.set(NetworkState::SyncTest);
+        next_state.set(GameState::Gameplay);
+    } else {
+        next_state.set(GameState::Menu);
+    }
 }
\ No newline at end of file
@@ -1,15 +1,25 @@
+use std::{collections::VecDeque, fs::File, hash::{Hash, Hasher}, io::Write};
+
 use crate::game::*;
+use crate::{FighterState, FixedVec2, Hitboxes, Hurtboxes, Projectile, RoundState, Throw, Combo, InputSettings};
 use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
 use bevy_matchbox::prelude::*;
-use bevy_ggrs::ggrs::{Config, PlayerHandle, self, InputStatus};
-use bevy_ggrs::PlayerInputs;
+use bevy_ggrs::ggrs::{Config, PlayerHandle, PlayerType, self, InputStatus, GGRSEvent, DesyncDetection};
+use bevy_ggrs::{PlayerInputs, Session};
+
+//how many frames of state dumps to keep around for desync logs
+const CHECKSUM_HISTORY_LENGTH: usize = 128;
+//GGRS' default max prediction window, synctest sessions fail to start unless the check distance is below it
+const MAX_PREDICTION_FRAMES: usize = 8;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash, Default, States)]
 pub enum NetworkState {
     #[default]
     Offline,
     Connecting,
-    Online
+    Online,
+    SyncTest
 }
 
 #[derive(Resource)]
@@ -22,10 +32,53 @@ pub struct IP {
     pub ip: String,
 }
 
+//launch with `--synctest [--check-distance N]` to resimulate every frame locally and compare checksums
+#[derive(Resource)]
+pub struct SyncTestConfig {
+    pub check_distance: usize,
+}
+
+impl Default for SyncTestConfig {
+    fn default() -> Self {
+        SyncTestConfig { check_distance: 7 }
+    }
+}
+
+impl SyncTestConfig {
+    //runs before the app (and its logger) exists, so problems go straight to stderr
+    pub fn from_args() -> Option<SyncTestConfig> {
+        let mut config: Option<SyncTestConfig> = None;
+        let mut check_distance: Option<usize> = None;
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--synctest" => {
+                    config = Some(SyncTestConfig::default());
+                }
+                "--check-distance" => {
+                    match args.next().and_then(|distance| distance.parse().ok()) {
+                        Some(distance) => check_distance = Some(distance),
+                        None => eprintln!("--check-distance needs a number of frames")
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (Some(config), Some(check_distance)) = (&mut config, check_distance) {
+            if check_distance >= MAX_PREDICTION_FRAMES {
+                eprintln!("--check-distance {} is too high, it has to be below the max prediction window of {} frames, using {}", check_distance, MAX_PREDICTION_FRAMES, MAX_PREDICTION_FRAMES - 1);
+            }
+            config.check_distance = check_distance.min(MAX_PREDICTION_FRAMES - 1);
+        }
+        config
+    }
+}
+
 #[derive(Debug)]
 pub struct GGRSConfig;
 impl Config for GGRSConfig {
     type Input = Inputs;
+    //bevy_ggrs keeps its own snapshots, GGRS only ever sees the checksum of them (see checksum_players)
     type State = u8;
     type Address = PeerId;
 }
@@ -57,7 +110,8 @@ pub fn wait_for_players(mut next_state: ResMut<NextState<NetworkState>>, mut com
     // create a GGRS P2P session
     let mut session_builder = ggrs::SessionBuilder::<GGRSConfig>::new()
         .with_num_players(num_players)
-        .with_input_delay(2);
+        .with_input_delay(2)
+        .with_desync_detection_mode(DesyncDetection::On { interval: 1 });
 
     for (i, player) in players.into_iter().enumerate() {
         session_builder = session_builder
@@ -76,20 +130,46 @@ pub fn wait_for_players(mut next_state: ResMut<NextState<NetworkState>>, mut com
     commands.insert_resource(bevy_ggrs::Session::P2P(ggrs_session));
 }
 
+pub fn start_synctest_session(mut commands: Commands, config: Res<SyncTestConfig>) {
+    info!("starting synctest session with a check distance of {}", config.check_distance);
+    let num_players = 2;
+    let mut session_builder = ggrs::SessionBuilder::<GGRSConfig>::new()
+        .with_num_players(num_players)
+        .with_check_distance(config.check_distance)
+        .with_input_delay(2);
+
+    for i in 0..num_players {
+        session_builder = session_builder
+            .add_player(PlayerType::Local, i)
+            .expect("failed to add player");
+    }
+
+    let ggrs_session = session_builder
+        .start_synctest_session()
+        .expect("failed to start synctest session");
+
+    commands.insert_resource(bevy_ggrs::Session::SyncTest(ggrs_session));
+}
+
 pub fn network_input(
-    _: In<PlayerHandle>,
+    handle: In<PlayerHandle>,
+    synctest: Option<Res<SyncTestConfig>>,
     keyboard_input: Res<Input<KeyCode>>,
     gamepads: Res<Gamepads>,
     button_inputs: Res<Input<GamepadButton>>,
     button_axes: Res<Axis<GamepadButton>>,
     axes: Res<Axis<GamepadAxis>>, 
 ) -> Inputs {
+    //both players are local in a synctest, only the first one gets the controls
+    if synctest.is_some() && handle.0 != 0 {
+        return Inputs::NONE;
+    }
     return input(keyboard_input, gamepads, button_inputs, button_axes, axes);
 }
 
-pub fn apply_inputs(inputs: Res<PlayerInputs<GGRSConfig>>, players: Query<(&mut Movable, &Player, &mut ActionComponent)>) {
+pub fn apply_inputs(inputs: Res<PlayerInputs<GGRSConfig>>, players: Query<(&mut Movable, &Player, &mut ActionComponent, &FighterState)>, settings: Res<InputSettings>) {
     let (localinputs, _inputstatus): (Vec<Inputs>, Vec<InputStatus>) = inputs.iter().cloned().unzip();
-    set_player_input(localinputs, players);
+    set_player_input(localinputs, players, &settings.0);
 }
 
 pub fn offline_apply_inputs(
@@ -98,10 +178,238 @@ pub fn offline_apply_inputs(
     button_inputs: Res<Input<GamepadButton>>,
     button_axes: Res<Axis<GamepadButton>>,
     axes: Res<Axis<GamepadAxis>>, 
-    players: Query<(&mut Movable, &Player, &mut ActionComponent)>){
+    players: Query<(&mut Movable, &Player, &mut ActionComponent, &FighterState)>,
+    settings: Res<InputSettings>){
     let mut inputs: Vec<Inputs> = vec![Inputs::NONE; players.iter().len()];
     if players.iter().len() > 0 {
         inputs[0] = input(keyboard_input, gamepads, button_inputs, button_axes, axes);//TEMP, is currently only going to P1 slot
-        set_player_input(inputs, players);
+        set_player_input(inputs, players, &settings.0);
     }
-}
\ No newline at end of file
+}
+
+//registered as a rollback resource with a reflected Hash, so bevy_ggrs folds it into the checksum it sends to GGRS
+//bevy_ggrs rehashes it (and FrameCount and ProjectileIds) with bevy's ahash, which isn't guaranteed to match across
+//targets, so online play and synctests only support both sides running the same build
+#[derive(Resource, Reflect, Default, Hash, Clone, Copy)]
+#[reflect(Resource, Hash)]
+pub struct Checksum {
+    pub value: u64
+}
+
+#[derive(Resource, Default)]
+pub struct ChecksumHistory {
+    pub frames: VecDeque<FrameSnapshot>
+}
+
+#[derive(Resource, Default)]
+pub struct DesyncWarning {
+    pub desyncs: Vec<String>
+}
+
+//copies of the simulated state, only formatted into a dump for the frame GGRS reports as desynced
+pub struct FrameSnapshot {
+    pub frame: u32,
+    pub checksum: u64,
+    pub round: RoundState,
+    pub players: Vec<PlayerSnapshot>,
+    pub projectiles: Vec<(Projectile, FixedVec2, Hitboxes)>
+}
+
+pub struct PlayerSnapshot {
+    pub handle: usize,
+    pub name: String,
+    pub movable: Movable,
+    pub actions: ActionComponent,
+    pub health: Health,
+    pub state: FighterState,
+    pub hitboxes: Hitboxes,
+    pub hurtboxes: Hurtboxes,
+    pub throw: Throw,
+    pub combo: Combo
+}
+
+impl FrameSnapshot {
+    pub fn dump(&self) -> String {
+        let mut dump = format!("frame: {}\nround: {:?}\n", self.frame, self.round);
+        for player in &self.players {
+            dump += &format!(
+                "player {} ({}):\n  movable: {:?}\n  actions: {:?}\n  health: {:?}\n  state: {:?}\n  hitboxes: {:?}\n  hurtboxes: {:?}\n  throw: {:?}\n  combo: {:?}\n",
+                player.handle, player.name, player.movable, player.actions, player.health, player.state, player.hitboxes, player.hurtboxes, player.throw, player.combo
+            );
+        }
+        for (projectile, position, hitboxes) in &self.projectiles {
+            dump += &format!(
+                "projectile {} (player {}):\n  position: {:?}\n  velocity: {:?}\n  frame: {}\n  hits left: {}\n  rearm: {}\n  hitboxes: {:?}\n",
+                projectile.info.name, projectile.owner, position, projectile.velocity, projectile.frame, projectile.hits_left, projectile.rearm_timer, hitboxes
+            );
+        }
+        return dump;
+    }
+}
+
+//FNV-1a, picked over the std/ahash hashers because it's cheap and doesn't depend on a random seed
+//integers go in little endian and usizes as u64, this doesn't make the checksum GGRS gets portable though (see Checksum)
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+pub fn checksum_players(
+    players: Query<(&Player, &Movable, &ActionComponent, &Health, &FighterState, &Hitboxes, &Hurtboxes, &Throw, &Combo)>,
+    projectiles: Query<(&Projectile, &Movable, &Hitboxes)>,
+    frame_count: Res<FrameCount>,
+    round_state: Res<RoundState>,
+    network_state: Res<State<NetworkState>>,
+    mut checksum: ResMut<Checksum>,
+    mut history: ResMut<ChecksumHistory>
+) {
+    //nothing to compare against offline
+    if *network_state.get() == NetworkState::Offline {
+        return;
+    }
+
+    let mut players: Vec<_> = players.iter().collect();
+    players.sort_by_key(|(player, _, _, _, _, _, _, _, _)| player.handle);
+
+    let mut hasher = StateHasher::default();
+    frame_count.frame.hash(&mut hasher);
+    round_state.hash(&mut hasher);
+    for (player, movable, actions, health, state, hitboxes, hurtboxes, throw, combo) in &players {
+        player.handle.hash(&mut hasher);
+        movable.hash(&mut hasher);
+        actions.hash(&mut hasher);
+        health.hash(&mut hasher);
+        state.hash(&mut hasher);
+        hitboxes.hash(&mut hasher);
+        hurtboxes.hash(&mut hasher);
+        throw.hash(&mut hasher);
+        combo.hash(&mut hasher);
+    }
+    //query order isn't stable across rollbacks, so each projectile is hashed on its own and the hashes are sorted
+    let mut projectile_hashes: Vec<u64> = projectiles.iter().map(|(projectile, movable, hitboxes)| {
+        let mut projectile_hasher = StateHasher::default();
+        projectile.hash(&mut projectile_hasher);
+        movable.position.hash(&mut projectile_hasher);
+        hitboxes.hash(&mut projectile_hasher);
+        projectile_hasher.finish()
+    }).collect();
+    projectile_hashes.sort();
+    projectile_hashes.hash(&mut hasher);
+    checksum.value = hasher.finish();
+
+    //synctest compares checksums itself, the history is only for desync logs
+    if *network_state.get() != NetworkState::Online {
+        return;
+    }
+    let mut projectile_snapshots: Vec<(Projectile, FixedVec2, Hitboxes)> = projectiles.iter()
+        .map(|(projectile, movable, hitboxes)| (projectile.to_owned(), movable.position, hitboxes.to_owned()))
+        .collect();
+    projectile_snapshots.sort_by_key(|(projectile, _, _)| projectile.id);
+    let snapshot = FrameSnapshot {
+        frame: frame_count.frame,
+        checksum: checksum.value,
+        round: round_state.to_owned(),
+        players: players.iter().map(|(player, movable, actions, health, state, hitboxes, hurtboxes, throw, combo)| PlayerSnapshot {
+            handle: player.handle,
+            name: player.fighter.name.to_owned(),
+            movable: (*movable).to_owned(),
+            actions: (*actions).to_owned(),
+            health: (*health).to_owned(),
+            state: **state,
+            hitboxes: (*hitboxes).to_owned(),
+            hurtboxes: (*hurtboxes).to_owned(),
+            throw: (*throw).to_owned(),
+            combo: (*combo).to_owned()
+        }).collect(),
+        projectiles: projectile_snapshots
+    };
+
+    //frames get simulated again during rollbacks, only the latest run of a frame is kept
+    history.frames.retain(|snapshot| snapshot.frame < frame_count.frame);
+    history.frames.push_back(snapshot);
+    while history.frames.len() > CHECKSUM_HISTORY_LENGTH {
+        history.frames.pop_front();
+    }
+}
+
+pub fn desync_system(
+    session: Option<ResMut<Session<GGRSConfig>>>,
+    history: Res<ChecksumHistory>,
+    mut warning: ResMut<DesyncWarning>
+) {
+    let Some(mut session) = session else {
+        return;
+    };
+    if let Session::P2P(session) = session.as_mut() {
+        for event in session.events() {
+            match event {
+                GGRSEvent::DesyncDetected { frame, local_checksum, remote_checksum, addr } => {
+                    let message = format!("Desync on frame {} with {:?}: local checksum {:x}, remote checksum {:x}", frame, addr, local_checksum, remote_checksum);
+                    error!("{}", message);
+
+                    let file_name = format!("desync_frame_{}.log", frame);
+                    let mut log = message.to_owned() + "\n\n";
+                    match history.frames.iter().find(|snapshot| snapshot.frame as i32 == frame) {
+                        Some(snapshot) => {
+                            log += &format!("local state checksum {:x}:\n{}", snapshot.checksum, snapshot.dump());
+                        }
+                        None => {
+                            log += "state for this frame is no longer in the history, dumping everything kept:\n";
+                            for snapshot in &history.frames {
+                                log += &format!("checksum {:x}:\n{}\n", snapshot.checksum, snapshot.dump());
+                            }
+                        }
+                    }
+                    match File::create(&file_name).and_then(|mut file| file.write_all(log.as_bytes())) {
+                        Ok(_) => warning.desyncs.push(format!("{} (state dumped to {})", message, file_name)),
+                        Err(err) => warning.desyncs.push(format!("{} (couldn't write {}: {})", message, file_name, err))
+                    }
+                }
+                _ => {
+                    info!("GGRS event: {:?}", event);
+                }
+            }
+        }
+    }
+}
+
+pub fn desync_warning_system(mut contexts: EguiContexts, mut warning: ResMut<DesyncWarning>) {
+    if warning.desyncs.len() == 0 {
+        return;
+    }
+    egui::Window::new("DESYNC DETECTED").show(contexts.ctx_mut(), |ui| {
+        for desync in &warning.desyncs {
+            ui.colored_label(egui::Color32::RED, desync);
+        }
+        if ui.button("Dismiss").clicked() {
+            warning.desyncs.clear();
+        }
+    });
+}
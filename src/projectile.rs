@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use bevy_ggrs::AddRollbackCommandExtension;
+use serde::{Deserialize, Serialize};
+
+use crate::{boxes_overlap, default_true, ActiveHitbox, AnimationData, FixedVec2, HitboxEffect, Hitboxes, Movable, Player, SpriteAtlas, SpriteRes};
+
+//projectiles are listed in the fighter's RON and spawned by name with Effect::SpawnProjectile
+#[derive(Debug, Default, Clone, Hash, Deserialize, Serialize, Reflect)]
+pub struct ProjectileInfo {
+    pub name: String,
+    pub sprite: String,
+    //where it spawns relative to the fighter, written facing right like everything else
+    #[serde(default)]
+    pub offset: FixedVec2,
+    //per frame
+    pub velocity: FixedVec2,
+    pub lifetime: i32,
+    pub hitbox: HitboxEffect,
+    #[serde(default = "default_hits")]
+    pub hits: i32,
+    //frames between hits for projectiles that hit more than once
+    #[serde(default = "default_hit_interval")]
+    pub hit_interval: i32,
+    #[serde(default = "default_true")]
+    pub destroy_on_hit: bool,
+    //clashing projectiles each lose a hit
+    #[serde(default = "default_true")]
+    pub clash: bool
+}
+
+fn default_hits() -> i32 {
+    1
+}
+
+fn default_hit_interval() -> i32 {
+    8
+}
+
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
+pub struct Projectile {
+    pub id: u32,
+    pub owner: usize,
+    pub info: ProjectileInfo,
+    pub velocity: FixedVec2,
+    pub frame: i32,
+    pub hits_left: i32,
+    pub rearm_timer: i32
+}
+
+//handed out in spawn order and rolled back, so resimulated projectiles get the same ids
+#[derive(Resource, Reflect, Default, Hash, Clone, Copy)]
+#[reflect(Resource, Hash)]
+pub struct ProjectileIds {
+    pub next: u32
+}
+
+//only the simulation components, projectile_sprite_system adds the sprite
+pub fn spawn_projectile(commands: &mut Commands, ids: &mut ProjectileIds, info: &ProjectileInfo, owner: &Player, movable: &Movable, atlas: &SpriteAtlas) {
+    let sign = movable.facing.sign();
+    let position = movable.position + FixedVec2::new(info.offset.x * sign, info.offset.y);
+    let velocity = FixedVec2::new(info.velocity.x * sign, info.velocity.y);
+    let mut hitbox = info.hitbox;
+    hitbox.offset.x *= sign;
+    hitbox.duration = info.lifetime;
+    let id = ids.next;
+    ids.next = ids.next.wrapping_add(1);
+    commands.spawn((
+        Projectile { id, owner: owner.handle, info: info.to_owned(), velocity, frame: 0, hits_left: info.hits, rearm_timer: 0 },
+        Movable { position, facing: movable.facing, ..default() },
+        Hitboxes { boxes: vec![ActiveHitbox { hitbox, frame: 0, has_hit: false }] },
+        AnimationData::new(info.sprite.to_owned(), atlas)
+    )).add_rollback();
+}
+
+//a rollback can bring back a despawned projectile, but only with its registered components, so this covers new and restored ones alike
+pub fn projectile_sprite_system(
+    mut commands: Commands,
+    projectiles: Query<(Entity, &Projectile, &Movable), Without<TextureAtlasSprite>>,
+    players: Query<&Player>,
+    sprites: Res<SpriteRes>
+) {
+    for (entity, projectile, movable) in &projectiles {
+        let Some(owner) = players.iter().find(|player| player.handle == projectile.owner) else {
+            continue;
+        };
+        let Some(atlas) = sprites.atlases.get(&owner.fighter.name.to_lowercase()) else {
+            continue;
+        };
+        commands.entity(entity).insert(SpriteSheetBundle {
+            transform: Transform::from_translation(movable.position.to_vec2().extend(1.0)),
+            texture_atlas: atlas.atlas.to_owned(),
+            sprite: TextureAtlasSprite::new(0),
+            ..default()
+        });
+    }
+}
+
+pub fn projectile_movement(mut projectiles: Query<(&mut Projectile, &mut Movable)>) {
+    for (mut projectile, mut movable) in &mut projectiles {
+        let velocity = projectile.velocity;
+        movable.position += velocity;
+        projectile.frame += 1;
+    }
+}
+
+//runs after apply_hits, counts down hits and clashes and removes spent projectiles
+pub fn projectile_system(mut commands: Commands, mut projectiles: Query<(Entity, &mut Projectile, &Movable, &mut Hitboxes)>) {
+    let mut armed: Vec<(Entity, u32, usize, FixedVec2, FixedVec2)> = vec![];
+    for (entity, projectile, movable, hitboxes) in &projectiles {
+        if !projectile.info.clash {
+            continue;
+        }
+        for active in &hitboxes.boxes {
+            if !active.has_hit {
+                armed.push((entity, projectile.id, projectile.owner, movable.position + active.hitbox.offset, active.hitbox.size));
+            }
+        }
+    }
+    //sorted so the clashes come out the same no matter what order the query runs in
+    armed.sort_by_key(|(_, id, owner, position, _)| (*owner, *id, position.x, position.y));
+    let mut clashed: Vec<Entity> = vec![];
+    for i in 0..armed.len() {
+        for j in (i + 1)..armed.len() {
+            let (a, _, a_owner, a_center, a_size) = armed[i];
+            let (b, _, b_owner, b_center, b_size) = armed[j];
+            if a_owner == b_owner || clashed.contains(&a) || clashed.contains(&b) {
+                continue;
+            }
+            if boxes_overlap(a_center, a_size, b_center, b_size) {
+                clashed.push(a);
+                clashed.push(b);
+            }
+        }
+    }
+
+    for (entity, mut projectile, _, mut hitboxes) in &mut projectiles {
+        let hit = hitboxes.boxes.iter().any(|active| active.has_hit);
+        if clashed.contains(&entity) {
+            for active in &mut hitboxes.boxes {
+                active.has_hit = true;
+            }
+        }
+        if (hit || clashed.contains(&entity)) && projectile.rearm_timer == 0 && projectile.hits_left > 0 {
+            projectile.hits_left -= 1;
+            projectile.rearm_timer = projectile.info.hit_interval.max(1);
+        }
+
+        let spent = projectile.hits_left <= 0 && (projectile.info.destroy_on_hit || clashed.contains(&entity));
+        if spent || projectile.frame >= projectile.info.lifetime {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+
+        if projectile.rearm_timer > 0 {
+            projectile.rearm_timer -= 1;
+            if projectile.rearm_timer == 0 && projectile.hits_left > 0 {
+                for active in &mut hitboxes.boxes {
+                    active.has_hit = false;
+                }
+            }
+        }
+    }
+}
+
+pub fn despawn_projectiles(mut commands: Commands, projectiles: Query<Entity, With<Projectile>>) {
+    for projectile in &projectiles {
+        commands.entity(projectile).despawn_recursive();
+    }
+}
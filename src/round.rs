@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{starting_position, ActionComponent, FighterState, GameState, Health, Hitboxes, Hurtboxes, Movable, Player, Projectile, Throw, Combo};
+
+const KO_PAUSE_FRAMES: i32 = 90;
+
+#[derive(Resource, Reflect, Debug, Clone, Hash)]
+#[reflect(Resource)]
+pub struct RoundState {
+    pub best_of: u32,
+    pub round: u32,
+    pub wins: Vec<u32>,
+    pub ko_timer: i32,
+    pub winner: Option<usize>,
+}
+
+impl Default for RoundState {
+    fn default() -> Self {
+        RoundState { best_of: 3, round: 1, wins: vec![0, 0], ko_timer: 0, winner: None }
+    }
+}
+
+impl RoundState {
+    pub fn rounds_to_win(&self) -> u32 {
+        self.best_of / 2 + 1
+    }
+}
+
+pub fn reset_round_state(mut round_state: ResMut<RoundState>) {
+    let best_of = round_state.best_of;
+    *round_state = RoundState { best_of, ..default() };
+}
+
+pub fn round_system(
+    mut commands: Commands,
+    mut round_state: ResMut<RoundState>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut players: Query<(&Player, &mut Health, &mut Movable, &mut ActionComponent, &mut FighterState, &mut Hitboxes, &mut Hurtboxes, &mut Throw, &mut Combo)>,
+    projectiles: Query<Entity, With<Projectile>>
+) {
+    if round_state.winner.is_some() {
+        return;
+    }
+
+    if round_state.ko_timer > 0 {
+        //KO'd players stay down until the next round starts
+        for (_, health, _, mut actions, _, _, _, _, _) in &mut players {
+            if health.current <= 0 {
+                actions.actions.clear();
+            }
+        }
+
+        round_state.ko_timer -= 1;
+        if round_state.ko_timer > 0 {
+            return;
+        }
+
+        let rounds_to_win = round_state.rounds_to_win();
+        let most_wins = round_state.wins.iter().max().copied().unwrap_or(0);
+        let mut winners: Vec<usize> = vec![];
+        for (handle, wins) in round_state.wins.iter().enumerate() {
+            if *wins == most_wins {
+                winners.push(handle);
+            }
+        }
+        //a double KO on match point keeps going until one player is ahead
+        if most_wins >= rounds_to_win && winners.len() == 1 {
+            round_state.winner = Some(winners[0]);
+            debug!("Player {} wins the match!", winners[0] + 1);
+            next_state.set(GameState::MatchEnd);
+            return;
+        }
+
+        round_state.round += 1;
+        debug!("Round {}!", round_state.round);
+        for projectile in &projectiles {
+            commands.entity(projectile).despawn_recursive();
+        }
+        for (player, mut health, mut movable, mut actions, mut state, mut hitboxes, mut hurtboxes, mut throw, mut combo) in &mut players {
+            health.current = health.max;
+            *movable = Movable { position: starting_position(player.handle), ..default() };
+            *actions = ActionComponent { ..default() };
+            *state = FighterState::Standing;
+            hitboxes.boxes.clear();
+            hurtboxes.modifiers.clear();
+            *throw = Throw { ..default() };
+            *combo = Combo { ..default() };
+        }
+        return;
+    }
+
+    let mut knocked_out = vec![false; round_state.wins.len()];
+    for (player, health, _, _, _, _, _, _, _) in &players {
+        if health.current <= 0 && player.handle < knocked_out.len() {
+            knocked_out[player.handle] = true;
+        }
+    }
+    if knocked_out.iter().any(|ko| *ko) {
+        debug!("KO!");
+        let double_ko = knocked_out.iter().all(|ko| *ko);
+        for (handle, ko) in knocked_out.iter().enumerate() {
+            if !ko || double_ko {
+                round_state.wins[handle] += 1;
+            }
+        }
+        round_state.ko_timer = KO_PAUSE_FRAMES;
+    }
+}
+
+pub fn despawn_players(mut commands: Commands, players: Query<Entity, With<Player>>) {
+    for player in &players {
+        commands.entity(player).despawn_recursive();
+    }
+}
+
+pub fn match_end_system(
+    mut contexts: EguiContexts,
+    round_state: Res<RoundState>,
+    mut next_state: ResMut<NextState<GameState>>
+) {
+    egui::Window::new("Match Over").show(contexts.ctx_mut(), |ui| {
+        if let Some(winner) = round_state.winner {
+            ui.label(format!("Player {} wins!", winner + 1));
+        }
+        for (handle, wins) in round_state.wins.iter().enumerate() {
+            ui.label(format!("Player {}: {} rounds", handle + 1, wins));
+        }
+        if ui.button("Back to Menu").clicked() {
+            next_state.set(GameState::Menu);
+        }
+    });
+}
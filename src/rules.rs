@@ -0,0 +1,91 @@
+use bevy::{prelude::*, utils::BoxedFuture, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}};
+use serde::{Deserialize, Serialize};
+
+//numbers that apply to every fighter, loaded from game.rules.ron
+#[derive(Debug, Deserialize, Serialize, TypeUuid, TypePath, Clone)]
+#[uuid = "6f1c2a9e-3b7d-4e58-a0c4-9d2e71b5f843"]
+#[serde(default)]
+pub struct GameRules {
+    //percent of damage each hit of a combo does, the first entry is the starter and the last one repeats
+    pub damage_scaling: Vec<i32>,
+    pub minimum_scaling: i32,
+    //hitstun lost for every hit already in the combo, it can go all the way to 0 so every combo ends
+    //has to be at least 1, the rules file is rejected otherwise since 0 would allow infinites
+    pub hitstun_decay: i32,
+    //juggle points a combo can spend on an airborne opponent
+    pub juggle_limit: i32,
+    pub counter_hit_hitstun: i32,
+    pub counter_hit_damage_percent: i32
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules {
+            damage_scaling: vec![100, 100, 80, 70, 60, 50, 40],
+            minimum_scaling: 10,
+            hitstun_decay: 1,
+            juggle_limit: 10,
+            counter_hit_hitstun: 8,
+            counter_hit_damage_percent: 10
+        }
+    }
+}
+
+impl GameRules {
+    pub fn scaling(&self, hits: u32) -> i32 {
+        let index = (hits as usize).min(self.damage_scaling.len().max(1) - 1);
+        return self.damage_scaling.get(index).copied().unwrap_or(100);
+    }
+    pub fn combo_hitstun(&self, hitstun: i32, hits: u32) -> i32 {
+        return (hitstun - self.hitstun_decay * hits as i32).max(0);
+    }
+    fn validate(&self) -> Result<(), String> {
+        if self.hitstun_decay < 1 {
+            return Err(format!("hitstun_decay has to be at least 1, got {}", self.hitstun_decay));
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Rules(pub GameRules);
+
+#[derive(Default)]
+pub struct GameRulesLoader;
+
+impl AssetLoader for GameRulesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let custom_asset = ron::de::from_bytes::<GameRules>(bytes)?;
+            if let Err(error) = custom_asset.validate() {
+                return Err(bevy::asset::Error::msg(format!("{}: {}", load_context.path().display(), error)));
+            }
+
+            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rules.ron"]
+    }
+}
+
+pub fn rules_setup(
+    asset_server: Res<AssetServer>,
+    game_rules: Res<Assets<GameRules>>,
+    mut rules: ResMut<Rules>
+){
+    let _handles: Vec<HandleUntyped> = asset_server.load_folder("./").unwrap();
+    for _handle in _handles {
+        let handle = _handle.typed_weak();
+        if let Some(loaded_rules) = game_rules.get(&handle) {
+            rules.0 = loaded_rules.to_owned();
+            return;
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::{prelude::*, utils::BoxedFuture, reflect::{TypePath, TypeUuid}, asset::{AssetLoader, LoadContext, LoadedAsset}};
+use serde::{Deserialize, Serialize};
+
+use crate::Fixed;
+
+#[derive(Debug, Deserialize, Serialize, TypeUuid, TypePath, Clone)]
+#[uuid = "a4d2e8b1-6c3f-4f0a-9b7e-2d51c8e03f96"]
+pub struct StageInfo {
+    pub name: String,
+    pub left_wall: Fixed,
+    pub right_wall: Fixed,
+    pub floor: Fixed
+}
+
+impl Default for StageInfo {
+    fn default() -> Self {
+        StageInfo { name: String::from("Training"), left_wall: Fixed::from_int(-200), right_wall: Fixed::from_int(200), floor: Fixed::from_int(-50) }
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Stage(pub StageInfo);
+
+#[derive(Default)]
+pub struct StageLoader;
+
+impl AssetLoader for StageLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let custom_asset = ron::de::from_bytes::<StageInfo>(bytes)?;
+
+            load_context.set_default_asset(LoadedAsset::new(custom_asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["stage.ron"]
+    }
+}
+
+pub fn stage_setup(
+    asset_server: Res<AssetServer>,
+    stages: Res<Assets<StageInfo>>,
+    mut stage: ResMut<Stage>
+){
+    let _handles: Vec<HandleUntyped> = asset_server.load_folder("./").unwrap();
+    for _handle in _handles {
+        let handle = _handle.typed_weak();
+        if let Some(stage_info) = stages.get(&handle) {
+            //TODO: stage select, for now the first stage found is used
+            stage.0 = stage_info.to_owned();
+            return;
+        }
+    }
+}
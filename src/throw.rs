@@ -0,0 +1,213 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{boxes_overlap, ActionComponent, AttackKind, AnimationData, FacingDirection, FighterState, Fixed, FixedVec2, Hitboxes, Hurtbox, Hurtboxes, Inputs, Movable, MovementData, Player, SpriteEffect, SpriteRes};
+
+const TECH_PUSHBACK: i32 = 40;
+const TECH_PUSHBACK_FRAMES: i32 = 12;
+
+//a grab box, on contact the attacker starts throw_move and the defender is held until the throw lets go
+#[derive(Debug, Default, Clone, Hash, Deserialize, Serialize, Reflect)]
+pub struct GrabEffect {
+    pub offset: FixedVec2,
+    pub size: FixedVec2,
+    pub duration: i32,
+    pub throw_move: String,
+    //how many frames the defender has to press their throw input and break it
+    #[serde(default = "default_tech_window")]
+    pub tech_window: i32
+}
+
+fn default_tech_window() -> i32 {
+    7
+}
+
+#[derive(Debug, Default, Clone, Hash, Reflect)]
+pub struct ActiveGrab {
+    pub grab: GrabEffect,
+    pub frame: i32,
+    //where the action that made the grab was in the stack and what frame it was on
+    pub action_depth: usize,
+    pub action_frame: i32
+}
+
+impl ActiveGrab {
+    //the action that made the grab is still the one running, it hasn't ended or been replaced
+    pub fn owned_by(&self, actions: &ActionComponent) -> bool {
+        return actions.actions.len() == self.action_depth
+            && actions.actions.last().is_some_and(|action| action.frame == self.action_frame + self.frame);
+    }
+}
+
+//victims are stored by player handle, and their position and sprite are set from the attacker's actions
+#[derive(Component, Reflect, Default, Debug, Clone, Hash)]
+#[reflect(Component)]
+pub struct Throw {
+    pub grabs: Vec<ActiveGrab>,
+    pub victim: Option<usize>,
+    pub victim_offset: FixedVec2,
+    pub victim_sprite: Option<SpriteEffect>
+}
+
+//grabs only connect with grounded opponents that aren't in stun or already being thrown
+fn throwable(movable: &Movable, state: &FighterState, actions: &ActionComponent, throw: &Throw) -> bool {
+    if !movable.grounded || throw.victim.is_some() || actions.invulnerable_to(AttackKind::Throw) {
+        return false;
+    }
+    match state {
+        FighterState::Standing | FighterState::Crouching | FighterState::LandingRecovery(_) => true,
+        _ => false
+    }
+}
+
+fn tech_pushback(direction: Fixed) -> MovementData {
+    return MovementData::new(Fixed::from_int(TECH_PUSHBACK), TECH_PUSHBACK_FRAMES, Fixed::ONE, 0, FixedVec2::new(direction, Fixed::ZERO));
+}
+
+pub fn grab_system(mut fighters: Query<(&Player, &mut Movable, &mut ActionComponent, &mut FighterState, &mut Hitboxes, &Hurtboxes, &mut Throw)>) {
+    let mut targets: Vec<(usize, FixedVec2, Vec<Hurtbox>)> = vec![];
+    for (player, movable, actions, state, _, hurtboxes, throw) in &fighters {
+        if throwable(&movable, &state, &actions, &throw) {
+            targets.push((player.handle, movable.position, hurtboxes.boxes.to_owned()));
+        }
+    }
+
+    let mut connections: Vec<(usize, usize, GrabEffect)> = vec![];
+    for (player, movable, actions, state, _, _, mut throw) in &mut fighters {
+        for active in &throw.grabs {
+            //a grab whose owner got hit or moved on can't connect anymore
+            if !state.actionable() || !active.owned_by(&actions) {
+                continue;
+            }
+            let grab = &active.grab;
+            let target = targets.iter().find(|(handle, position, hurtboxes)| *handle != player.handle && hurtboxes.iter().any(|hurtbox| boxes_overlap(
+                movable.position + grab.offset, grab.size,
+                *position + hurtbox.offset, hurtbox.size
+            )));
+            if let Some((victim, _, _)) = target {
+                connections.push((player.handle, *victim, grab.to_owned()));
+                break;
+            }
+        }
+        throw.grabs.retain_mut(|active| {
+            active.frame += 1;
+            return active.frame < active.grab.duration;
+        });
+    }
+    connections.sort_by_key(|(attacker, _, _)| *attacker);
+
+    let positions: Vec<(usize, FixedVec2)> = fighters.iter().map(|(player, movable, _, _, _, _, _)| (player.handle, movable.position)).collect();
+    for (attacker, victim, grab) in &connections {
+        //both players grabbing each other on the same frame is a tech
+        let clash = connections.iter().any(|(other_attacker, other_victim, _)| other_attacker == victim && other_victim == attacker);
+        for (player, mut movable, mut actions, mut state, mut hitboxes, _, mut throw) in &mut fighters {
+            if player.handle == *attacker {
+                throw.grabs.clear();
+                if clash {
+                    actions.actions.clear();
+                    let away = positions.iter().find(|(handle, _)| handle == victim).map_or(-movable.facing.sign(), |(_, position)| {
+                        if position.x < movable.position.x { Fixed::ONE } else { -Fixed::ONE }
+                    });
+                    movable.movements.push(tech_pushback(away));
+                    continue;
+                }
+                if let Some(throw_move) = player.fighter.moves.iter().find(|fighter_move| fighter_move.name == grab.throw_move) {
+                    debug!("Move {} started!", throw_move.name);
+                    actions.start_move(throw_move);
+                } else {
+                    warn!("Tried to throw with move {}, but {} doesn't have it", grab.throw_move, player.fighter.name);
+                    actions.actions.clear();
+                }
+                hitboxes.boxes.clear();
+                throw.victim = Some(*victim);
+                throw.victim_offset = FixedVec2::new(player.fighter.pushbox.size.x, Fixed::ZERO);
+                throw.victim_sprite = None;
+            } else if player.handle == *victim && !clash {
+                actions.actions.clear();
+                hitboxes.boxes.clear();
+                throw.grabs.clear();
+                movable.movements.clear();
+                movable.yspeed = Fixed::ZERO;
+                *state = FighterState::Thrown(grab.tech_window);
+            }
+        }
+    }
+}
+
+//holds thrown fighters in place, and lets go when they tech, get hit out of it or the throw ends
+pub fn throw_system(
+    mut fighters: Query<(&Player, &mut Movable, &mut ActionComponent, &mut FighterState, &mut Throw, Option<&mut AnimationData>)>,
+    sprites: Res<SpriteRes>
+) {
+    let mut victims: Vec<(usize, FighterState, bool)> = vec![];
+    for (player, movable, _, state, _, _) in &fighters {
+        if let FighterState::Thrown(_) = *state {
+            let tech_input = player.fighter.throw_input;
+            let teching = movable.input.current().has(&tech_input) && movable.input.pressed() & tech_input != Inputs::NONE;
+            victims.push((player.handle, *state, teching));
+        }
+    }
+
+    //(victim, attacker position, attacker facing, offset, sprite)
+    let mut holds: Vec<(usize, FixedVec2, FacingDirection, FixedVec2, Option<SpriteEffect>)> = vec![];
+    let mut released: Vec<(usize, Option<Fixed>)> = vec![];
+    for (_, mut movable, mut actions, _, mut throw, _) in &mut fighters {
+        let Some(victim) = throw.victim else {
+            continue;
+        };
+        let victim_state = victims.iter().find(|(handle, _, _)| *handle == victim);
+        match victim_state {
+            Some((_, FighterState::Thrown(tech_frames), teching)) if *teching && *tech_frames > 0 => {
+                debug!("Throw teched!");
+                let direction = -movable.facing.sign();
+                actions.actions.clear();
+                movable.movements.push(tech_pushback(direction));
+                throw.victim = None;
+                released.push((victim, Some(-direction)));
+            }
+            Some(_) if actions.actions.len() > 0 => {
+                let sprite = throw.victim_sprite.take();
+                holds.push((victim, movable.position, movable.facing, throw.victim_offset, sprite));
+            }
+            Some(_) => {
+                throw.victim = None;
+                released.push((victim, None));
+            }
+            //hit out of the throw or otherwise let go already
+            None => {
+                throw.victim = None;
+            }
+        }
+    }
+
+    for (player, mut movable, _, mut state, _, animation) in &mut fighters {
+        if let Some((_, direction)) = released.iter().find(|(handle, _)| *handle == player.handle) {
+            *state = FighterState::neutral(movable.grounded);
+            if let Some(direction) = direction {
+                movable.movements.push(tech_pushback(*direction));
+            }
+            continue;
+        }
+        let Some((_, position, facing, offset, sprite)) = holds.iter().find(|(handle, _, _, _, _)| *handle == player.handle) else {
+            //nobody is holding on to this fighter anymore
+            if let FighterState::Thrown(_) = *state {
+                *state = FighterState::neutral(movable.grounded);
+            }
+            continue;
+        };
+        movable.position = *position + FixedVec2::new(offset.x * facing.sign(), offset.y);
+        movable.facing = match facing {
+            FacingDirection::Right => FacingDirection::Left,
+            FacingDirection::Left => FacingDirection::Right
+        };
+        movable.yspeed = Fixed::ZERO;
+        if let (Some(sprite), Some(mut animation)) = (sprite, animation) {
+            if let Some(atlas) = sprites.atlases.get(&player.fighter.name.to_lowercase()) {
+                animation.play(&sprite.sprite, sprite.speed, sprite.mode, atlas);
+                if let Some(frame) = sprite.frame {
+                    animation.set_frame(frame);
+                }
+            }
+        }
+    }
+}